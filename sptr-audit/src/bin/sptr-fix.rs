@@ -0,0 +1,93 @@
+//! `sptr-fix [PATH]...`
+//!
+//! Rewrites the mechanical provenance-breaking casts in the Rust sources under the given
+//! files or directories (default: the current directory) to use sptr's `Strict` APIs, and
+//! prints the changes as a unified diff, which can be applied with `git apply` or `patch -p1`.
+//! Casts that need a human get a `// TODO(sptr-fix): ...` comment instead.
+//!
+//! Exits with 0 if nothing needed changing, 1 if a diff was printed, and 2 if some files
+//! couldn't be read or parsed.
+
+use std::path::PathBuf;
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("usage: sptr-fix [PATH]...");
+    eprintln!();
+    eprintln!("Prints a diff migrating pointer<->integer casts to sptr's Strict APIs.");
+    process::exit(2);
+}
+
+fn main() {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for arg in std::env::args_os().skip(1) {
+        if arg == "-h" || arg == "--help" {
+            usage();
+        }
+        roots.push(arg.into());
+    }
+    if roots.is_empty() {
+        roots.push(".".into());
+    }
+
+    let mut failed = false;
+    let mut files = Vec::new();
+    for root in &roots {
+        if let Err(err) = sptr_audit::collect_sources(root, &mut files) {
+            eprintln!("{}: {}", root.display(), err);
+            failed = true;
+        }
+    }
+
+    let mut changed = 0;
+    for file in &files {
+        let src = match std::fs::read_to_string(file) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failed = true;
+                continue;
+            }
+        };
+        let fixed = match sptr_audit::fix_source(&src) {
+            Ok(fixed) => fixed,
+            Err(err) => {
+                let start = err.span().start();
+                eprintln!(
+                    "{}:{}:{}: parse error: {}",
+                    file.display(),
+                    start.line,
+                    start.column + 1,
+                    err
+                );
+                failed = true;
+                continue;
+            }
+        };
+        if fixed == src {
+            continue;
+        }
+        changed += 1;
+
+        let path = file.strip_prefix(".").unwrap_or(file).display().to_string();
+        let diff = similar::TextDiff::from_lines(&src, &fixed);
+        print!(
+            "{}",
+            diff.unified_diff()
+                .context_radius(3)
+                .header(&format!("a/{}", path), &format!("b/{}", path))
+        );
+    }
+
+    eprintln!(
+        "sptr-fix: changed {} of {} file{}",
+        changed,
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+    );
+    if failed {
+        process::exit(2);
+    } else if changed > 0 {
+        process::exit(1);
+    }
+}
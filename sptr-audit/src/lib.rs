@@ -0,0 +1,1128 @@
+//! Finds the pointer<->integer casts that break strict provenance, so they can be migrated
+//! to [sptr](https://docs.rs/sptr)'s `Strict` APIs.
+//!
+//! This is a purely syntactic analysis (there's no type information without running the
+//! compiler), so it relies on heuristics to decide what is a pointer and what is an integer:
+//!
+//! * Pointers are casts to `*const T`/`*mut T`, calls to things like `as_ptr()`, `cast()`,
+//!   `ptr::null()` and `addr_of!`, and locals that were declared with a pointer type or
+//!   initialized with one of the above.
+//! * Integers are integer literals, casts to integer types, arithmetic on integers, and locals
+//!   that were declared with an integer type or initialized with one of the above.
+//!
+//! Anything it can't classify is ignored, so it prefers false negatives to false positives:
+//! `len as usize` and `&x as *const T` are fine, and so aren't reported.
+
+use proc_macro2::{LineColumn, Span};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{BinOp, Expr, GenericArgument, Lit, Pat, PathArguments, Type, UnOp};
+
+/// Which `sptr` API a cast should be migrated to.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Fix {
+    /// `ptr as usize` that should be `ptr.addr()`.
+    Addr,
+    /// `int as *const T` with no pointer in sight, which needs the original pointer to be
+    /// exposed with `expose_addr()` and this to be `sptr::from_exposed_addr(int)`
+    /// (or, better, to be restructured to keep the original pointer around).
+    ExposeAddr,
+    /// `(ptr as usize + n) as *const T` that should be `ptr.with_addr(..)`/`ptr.map_addr(..)`.
+    WithAddr,
+    /// `0x1000 as *const T` that should be `sptr::invalid(0x1000)`.
+    Invalid,
+}
+
+impl Fix {
+    /// A short human-readable description of the fix.
+    pub fn description(self) -> &'static str {
+        match self {
+            Fix::Addr => "needs addr",
+            Fix::ExposeAddr => "needs expose_addr",
+            Fix::WithAddr => "needs with_addr",
+            Fix::Invalid => "needs invalid",
+        }
+    }
+}
+
+impl std::fmt::Display for Fix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// A provenance-breaking cast found in some source code.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Finding {
+    /// The 1-based line of the start of the cast.
+    pub line: usize,
+    /// The 1-based column of the start of the cast.
+    pub column: usize,
+    /// The 1-based line of the end of the cast.
+    pub end_line: usize,
+    /// The 1-based column just past the end of the cast.
+    pub end_column: usize,
+    /// How the cast should be fixed.
+    pub fix: Fix,
+    /// Whether the cast is a `transmute` rather than an `as` cast.
+    pub transmute: bool,
+    /// The byte offset of the start of the cast.
+    pub start: usize,
+    /// The byte offset just past the end of the cast.
+    pub end: usize,
+    /// The source text of the cast (truncated to its first line).
+    pub snippet: String,
+    /// The code to replace the cast with, if this is one of the mechanical cases.
+    pub suggestion: Option<String>,
+}
+
+/// Finds all the provenance-breaking casts in a Rust source file.
+///
+/// Findings are sorted by position.
+pub fn audit_source(src: &str) -> syn::Result<Vec<Finding>> {
+    let file = syn::parse_file(src)?;
+    let mut auditor = Auditor {
+        src,
+        line_starts: line_starts(src),
+        findings: Vec::new(),
+        scopes: vec![Scope::default()],
+    };
+    auditor.visit_file(&file);
+    let mut findings = auditor.findings;
+    findings.sort_by_key(|f| (f.line, f.column));
+    Ok(findings)
+}
+
+/// Rewrites the mechanical provenance-breaking casts in a Rust source file to use
+/// sptr's `Strict` APIs, and returns the new source.
+///
+/// * `ptr as usize` becomes `ptr.addr()`.
+/// * `(ptr as usize + n) as *mut T` becomes `ptr.map_addr(|a| a + n)`.
+/// * `0x1000 as *const T` becomes `sptr::invalid::<T>(0x1000)`.
+///
+/// Everything else (exposed addresses, transmutes, round-trips through locals, ...) needs a
+/// human, so it's left alone with a `// TODO(sptr-fix): ...` comment above the statement it's
+/// in (or no comment, if that line starts inside a multi-line string literal). If any `addr`
+/// or `map_addr` calls were introduced, `use sptr::Strict;` and
+/// `#![allow(unstable_name_collisions)]` are added to the top of the file if they're missing.
+///
+/// Running this on its own output only adds things that are still missing.
+pub fn fix_source(src: &str) -> syn::Result<String> {
+    let file = syn::parse_file(src)?;
+    let findings = audit_source(src)?;
+    let starts = line_starts(src);
+    let mut statements = Statements {
+        src,
+        line_starts: &starts,
+        ranges: Vec::new(),
+    };
+    statements.visit_file(&file);
+    let literals = multi_line_literals(src, &starts)?;
+
+    // (offset, length to replace, replacement), applied back to front.
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    let mut needs_strict = false;
+    let mut replaced_until = 0;
+    for finding in &findings {
+        // Findings can be nested (a transmute of a cast); only rewrite the outermost.
+        if finding.start < replaced_until {
+            continue;
+        }
+        match &finding.suggestion {
+            Some(suggestion) => {
+                // A round-trip that doesn't touch the address is just the pointer again.
+                needs_strict |= suggestion.contains(".addr()") || suggestion.contains(".map_addr(");
+                edits.push((
+                    finding.start,
+                    finding.end - finding.start,
+                    suggestion.clone(),
+                ));
+                replaced_until = finding.end;
+            }
+            None => {
+                // Comment the innermost statement containing the cast, so the comment
+                // doesn't land in the middle of an expression that spans several lines.
+                let anchor = statements
+                    .ranges
+                    .iter()
+                    .filter(|&&(start, end)| start <= finding.start && finding.end <= end)
+                    .map(|&(start, _)| start)
+                    .max()
+                    .unwrap_or(finding.start);
+                let line = starts.partition_point(|&start| start <= anchor);
+                let line_start = starts[line - 1];
+                if literals
+                    .iter()
+                    .any(|&(start, end)| start < line_start && line_start < end)
+                {
+                    continue;
+                }
+                let text = &src[line_start..];
+                let indent = &text[..text.len() - text.trim_start().len()];
+                let comment = format!("// TODO(sptr-fix): {}: `{}`", finding.fix, finding.snippet);
+                let already_there =
+                    line >= 2 && src[starts[line - 2]..line_start].trim() == comment;
+                if !already_there {
+                    edits.push((line_start, 0, format!("{}{}\n", indent, comment)));
+                }
+            }
+        }
+    }
+
+    if needs_strict {
+        // Insert after the crate docs and attributes, which have to come first.
+        let inner_attrs = file
+            .attrs
+            .iter()
+            .filter(|attr| matches!(attr.style, syn::AttrStyle::Inner(_)));
+        let header_end = inner_attrs.clone().next_back().map_or(0, |attr| {
+            let end = byte_offset(src, &starts, attr.span().end());
+            src[end..].find('\n').map_or(src.len(), |i| end + i + 1)
+        });
+
+        let mut header = String::new();
+        let has_allow = inner_attrs.clone().any(|attr| {
+            attr.path().is_ident("allow")
+                && byte_range(src, &starts, attr.span()).contains("unstable_name_collisions")
+        });
+        if !has_allow {
+            header.push_str("#![allow(unstable_name_collisions)]\n");
+        }
+        let has_use = file.items.iter().any(|item| match item {
+            syn::Item::Use(item) => {
+                let text: String = byte_range(src, &starts, item.span())
+                    .split_whitespace()
+                    .collect();
+                text.contains("sptr::Strict") || text.contains("sptr::{Strict")
+            }
+            _ => false,
+        });
+        if !has_use {
+            header.push_str("use sptr::Strict;\n");
+        }
+        if !header.is_empty() {
+            if header_end > 0 {
+                header.insert(0, '\n');
+            } else {
+                header.push('\n');
+            }
+            edits.push((header_end, 0, header));
+        }
+    }
+
+    // Stable sort, so insertions at the same offset stay in order.
+    edits.sort_by_key(|&(start, _, _)| start);
+    let mut out = src.to_owned();
+    for (start, len, text) in edits.into_iter().rev() {
+        out.replace_range(start..start + len, &text);
+    }
+    Ok(out)
+}
+
+/// Collects all the `.rs` files under `path` (or `path` itself, if it's a file), in a
+/// stable order, skipping hidden directories and `target`.
+pub fn collect_sources(path: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        out.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                collect_sources(&path, out)?;
+            }
+        } else if name.ends_with(".rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The byte offset of the start of each line.
+fn line_starts(src: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+/// Converts a span location (with a column in chars) to a byte offset.
+fn byte_offset(src: &str, line_starts: &[usize], loc: LineColumn) -> usize {
+    let line_start = line_starts[loc.line - 1];
+    let line = &src[line_start..];
+    line_start
+        + line
+            .char_indices()
+            .nth(loc.column)
+            .map_or(line.len(), |(i, _)| i)
+}
+
+/// The source text of a span.
+fn byte_range<'src>(src: &'src str, line_starts: &[usize], span: Span) -> &'src str {
+    &src[byte_offset(src, line_starts, span.start())..byte_offset(src, line_starts, span.end())]
+}
+
+/// The byte ranges of the string literals that span more than one line, which comments
+/// can't be inserted into. `syn` doesn't see the literals inside macro invocations, so this
+/// walks the raw tokens instead.
+fn multi_line_literals(src: &str, line_starts: &[usize]) -> syn::Result<Vec<(usize, usize)>> {
+    fn walk(
+        tokens: proc_macro2::TokenStream,
+        src: &str,
+        line_starts: &[usize],
+        out: &mut Vec<(usize, usize)>,
+    ) {
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Group(group) => walk(group.stream(), src, line_starts, out),
+                proc_macro2::TokenTree::Literal(lit) => {
+                    let span = lit.span();
+                    if span.start().line != span.end().line {
+                        out.push((
+                            byte_offset(src, line_starts, span.start()),
+                            byte_offset(src, line_starts, span.end()),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let tokens: proc_macro2::TokenStream = src.parse()?;
+    let mut out = Vec::new();
+    walk(tokens, src, line_starts, &mut out);
+    Ok(out)
+}
+
+/// Collects the byte ranges of every statement and item, for anchoring comments.
+struct Statements<'a> {
+    src: &'a str,
+    line_starts: &'a [usize],
+    ranges: Vec<(usize, usize)>,
+}
+
+impl Statements<'_> {
+    fn push(&mut self, span: Span) {
+        self.ranges.push((
+            byte_offset(self.src, self.line_starts, span.start()),
+            byte_offset(self.src, self.line_starts, span.end()),
+        ));
+    }
+}
+
+impl<'ast> Visit<'ast> for Statements<'_> {
+    fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+        self.push(stmt.span());
+        visit::visit_stmt(self, stmt);
+    }
+
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        self.push(item.span());
+        visit::visit_item(self, item);
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast syn::ImplItem) {
+        self.push(item.span());
+        visit::visit_impl_item(self, item);
+    }
+
+    fn visit_trait_item(&mut self, item: &'ast syn::TraitItem) {
+        self.push(item.span());
+        visit::visit_trait_item(self, item);
+    }
+}
+
+#[derive(Default)]
+struct Scope {
+    pointers: HashSet<String>,
+    ints: HashSet<String>,
+    /// The subset of `ints` that were computed from a `ptr as usize` cast.
+    addrs: HashSet<String>,
+    /// The source text of the types of `pointers`, where we know them.
+    pointer_types: HashMap<String, String>,
+}
+
+struct Auditor<'src> {
+    src: &'src str,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    findings: Vec<Finding>,
+    scopes: Vec<Scope>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Local {
+    Pointer,
+    Int,
+    /// An int computed from a `ptr as usize` cast.
+    Addr,
+}
+
+const INT_TYPES: &[&str] = &[
+    "usize", "isize", "u8", "u16", "u32", "u64", "u128", "i8", "i16", "i32", "i64", "i128",
+];
+
+/// Methods that return a pointer no matter what they're called on.
+const POINTER_METHODS: &[&str] = &[
+    "as_ptr",
+    "as_mut_ptr",
+    "cast",
+    "cast_mut",
+    "cast_const",
+    "with_addr",
+    "map_addr",
+];
+
+/// Methods that return a pointer if they're called on a pointer (and an int on an int).
+const OFFSET_METHODS: &[&str] = &[
+    "add",
+    "sub",
+    "offset",
+    "wrapping_add",
+    "wrapping_sub",
+    "wrapping_offset",
+    "byte_add",
+    "byte_sub",
+    "byte_offset",
+    "wrapping_byte_add",
+    "wrapping_byte_sub",
+    "wrapping_byte_offset",
+];
+
+/// Functions that return a pointer.
+const POINTER_FNS: &[&str] = &[
+    "null",
+    "null_mut",
+    "invalid",
+    "invalid_mut",
+    "from_exposed_addr",
+    "from_exposed_addr_mut",
+];
+
+/// Methods that return an integer derived from a pointer's address.
+const ADDR_METHODS: &[&str] = &["addr", "expose_addr"];
+
+fn is_int_type(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path
+            .path
+            .get_ident()
+            .is_some_and(|ident| INT_TYPES.iter().any(|t| ident == t)),
+        Type::Paren(paren) => is_int_type(&paren.elem),
+        Type::Group(group) => is_int_type(&group.elem),
+        _ => false,
+    }
+}
+
+fn is_ptr_type(ty: &Type) -> bool {
+    match ty {
+        Type::Ptr(_) => true,
+        Type::Paren(paren) => is_ptr_type(&paren.elem),
+        Type::Group(group) => is_ptr_type(&group.elem),
+        _ => false,
+    }
+}
+
+fn is_arith(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Add(_)
+            | BinOp::Sub(_)
+            | BinOp::Mul(_)
+            | BinOp::Div(_)
+            | BinOp::Rem(_)
+            | BinOp::BitAnd(_)
+            | BinOp::BitOr(_)
+            | BinOp::BitXor(_)
+            | BinOp::Shl(_)
+            | BinOp::Shr(_)
+    )
+}
+
+/// Strips parens and invisible groups.
+fn strip(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Paren(paren) => strip(&paren.expr),
+        Expr::Group(group) => strip(&group.expr),
+        _ => expr,
+    }
+}
+
+fn last_segment_is(path: &syn::Path, names: &[&str]) -> bool {
+    path.segments
+        .last()
+        .is_some_and(|seg| names.iter().any(|n| seg.ident == n))
+}
+
+/// Is this an integer constant like `0x1000` or `1 << 12`?
+fn is_int_literal(expr: &Expr) -> bool {
+    match strip(expr) {
+        Expr::Lit(lit) => matches!(lit.lit, Lit::Int(_)),
+        Expr::Binary(bin) => {
+            is_arith(&bin.op) && is_int_literal(&bin.left) && is_int_literal(&bin.right)
+        }
+        Expr::Unary(unary) => {
+            matches!(unary.op, UnOp::Neg(_) | UnOp::Not(_)) && is_int_literal(&unary.expr)
+        }
+        Expr::Cast(cast) => is_int_type(&cast.ty) && is_int_literal(&cast.expr),
+        _ => false,
+    }
+}
+
+/// The generic arguments of a `transmute::<Src, Dst>` call, if any.
+fn transmute_types(call: &syn::ExprCall) -> Option<(&Type, &Type)> {
+    let path = match strip(&call.func) {
+        Expr::Path(path) => &path.path,
+        _ => return None,
+    };
+    let args = match &path.segments.last()?.arguments {
+        PathArguments::AngleBracketed(args) => &args.args,
+        _ => return None,
+    };
+    let mut types = args.iter().filter_map(|arg| match arg {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    });
+    Some((types.next()?, types.next()?))
+}
+
+impl<'src> Auditor<'src> {
+    /// Looks up what kind of local `expr` is, if it's a local we know about.
+    fn local(&self, expr: &Expr) -> Option<Local> {
+        let ident = match strip(expr) {
+            Expr::Path(path) if path.qself.is_none() => path.path.get_ident()?.to_string(),
+            _ => return None,
+        };
+        // Innermost binding wins, so that shadowing works.
+        for scope in self.scopes.iter().rev() {
+            if scope.pointers.contains(&ident) {
+                return Some(Local::Pointer);
+            }
+            if scope.addrs.contains(&ident) {
+                return Some(Local::Addr);
+            }
+            if scope.ints.contains(&ident) {
+                return Some(Local::Int);
+            }
+        }
+        None
+    }
+
+    fn is_pointer(&self, expr: &Expr) -> bool {
+        match strip(expr) {
+            Expr::Cast(cast) => is_ptr_type(&cast.ty),
+            Expr::MethodCall(call) => {
+                let method = call.method.to_string();
+                POINTER_METHODS.contains(&&*method)
+                    || (OFFSET_METHODS.contains(&&*method) && self.is_pointer(&call.receiver))
+            }
+            Expr::Call(call) => match strip(&call.func) {
+                Expr::Path(path) => last_segment_is(&path.path, POINTER_FNS),
+                _ => false,
+            },
+            Expr::Macro(mac) => last_segment_is(&mac.mac.path, &["addr_of", "addr_of_mut"]),
+            Expr::RawAddr(_) => true,
+            expr => self.local(expr) == Some(Local::Pointer),
+        }
+    }
+
+    fn is_int(&self, expr: &Expr) -> bool {
+        match strip(expr) {
+            Expr::Lit(lit) => matches!(lit.lit, Lit::Int(_)),
+            Expr::Cast(cast) => is_int_type(&cast.ty),
+            Expr::Binary(bin) => {
+                is_arith(&bin.op) && (self.is_int(&bin.left) || self.is_int(&bin.right))
+            }
+            Expr::Unary(unary) => {
+                matches!(unary.op, UnOp::Neg(_) | UnOp::Not(_)) && self.is_int(&unary.expr)
+            }
+            Expr::MethodCall(call) => {
+                let method = call.method.to_string();
+                ADDR_METHODS.contains(&&*method)
+                    || (OFFSET_METHODS.contains(&&*method) && self.is_int(&call.receiver))
+            }
+            expr => matches!(self.local(expr), Some(Local::Int) | Some(Local::Addr)),
+        }
+    }
+
+    /// Is this a `ptr as usize` cast?
+    fn is_ptr_to_int(&self, expr: &Expr) -> bool {
+        match strip(expr) {
+            Expr::Cast(cast) => is_int_type(&cast.ty) && self.is_pointer(&cast.expr),
+            _ => false,
+        }
+    }
+
+    /// Does this integer expression contain a `ptr as usize` cast (or a local computed
+    /// from one)?
+    fn contains_ptr_to_int(&self, expr: &Expr) -> bool {
+        match strip(expr) {
+            Expr::Path(_) => self.local(expr) == Some(Local::Addr),
+            Expr::Cast(cast) => {
+                self.is_ptr_to_int(expr)
+                    || (is_int_type(&cast.ty) && self.contains_ptr_to_int(&cast.expr))
+            }
+            Expr::Binary(bin) => {
+                self.contains_ptr_to_int(&bin.left) || self.contains_ptr_to_int(&bin.right)
+            }
+            Expr::Unary(unary) => self.contains_ptr_to_int(&unary.expr),
+            Expr::MethodCall(call) => {
+                self.contains_ptr_to_int(&call.receiver)
+                    || call.args.iter().any(|arg| self.contains_ptr_to_int(arg))
+            }
+            _ => false,
+        }
+    }
+
+    /// Converts a span location to a byte offset.
+    fn offset(&self, loc: LineColumn) -> usize {
+        byte_offset(self.src, &self.line_starts, loc)
+    }
+
+    /// The source text of a span.
+    fn text(&self, span: Span) -> &'src str {
+        byte_range(self.src, &self.line_starts, span)
+    }
+
+    /// The source text of an expression, parenthesized if it's needed to call a method on it.
+    fn receiver_text(&self, expr: &Expr) -> String {
+        let text = self.text(expr.span());
+        match expr {
+            Expr::Path(_)
+            | Expr::MethodCall(_)
+            | Expr::Call(_)
+            | Expr::Paren(_)
+            | Expr::Field(_)
+            | Expr::Index(_)
+            | Expr::Macro(_) => text.to_owned(),
+            _ => format!("({})", text),
+        }
+    }
+
+    /// The source text of the type of a pointer expression, if we know it.
+    fn pointer_type(&self, expr: &Expr) -> Option<String> {
+        match strip(expr) {
+            Expr::Cast(cast) => Some(self.text(cast.ty.span()).to_owned()),
+            Expr::Path(path) => {
+                let ident = path.path.get_ident()?.to_string();
+                for scope in self.scopes.iter().rev() {
+                    if scope.pointers.contains(&ident) {
+                        return scope.pointer_types.get(&ident).cloned();
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, span: Span, fix: Fix, transmute: bool, suggestion: Option<String>) {
+        let start = span.start();
+        let end = span.end();
+        let snippet = self.text(span);
+        let snippet = match snippet.find('\n') {
+            Some(newline) => format!("{} ...", snippet[..newline].trim_end()),
+            None => snippet.to_owned(),
+        };
+        self.findings.push(Finding {
+            line: start.line,
+            column: start.column + 1,
+            end_line: end.line,
+            end_column: end.column + 1,
+            start: self.offset(start),
+            end: self.offset(end),
+            fix,
+            transmute,
+            snippet,
+            suggestion,
+        });
+    }
+
+    /// Suggests `ptr.addr()` for `ptr as usize`.
+    fn suggest_addr(&self, cast: &syn::ExprCast) -> String {
+        let ty = self.text(cast.ty.span());
+        let addr = format!("{}.addr()", self.receiver_text(&cast.expr));
+        if ty == "usize" {
+            addr
+        } else {
+            format!("{} as {}", addr, ty)
+        }
+    }
+
+    /// Suggests `ptr.map_addr(|a| ...)` for `(ptr as usize ...) as *const T`, if there's
+    /// exactly one `ptr as usize` cast in there.
+    fn suggest_map_addr(&self, cast: &syn::ExprCast) -> Option<String> {
+        let mut inner = Vec::new();
+        if !self.find_ptr_to_int(&cast.expr, &mut inner) || inner.len() != 1 {
+            return None;
+        }
+        let inner = inner[0];
+        if self.text(inner.ty.span()) != "usize" {
+            return None;
+        }
+        let ptr = &inner.expr;
+        let target = self.text(cast.ty.span());
+        let same_type = self
+            .pointer_type(ptr)
+            .is_some_and(|ty| ty.split_whitespace().eq(target.split_whitespace()));
+
+        // `(ptr as usize) as *const T` doesn't change the address at all.
+        if let Expr::Cast(body) = strip(&cast.expr) {
+            if std::ptr::eq(body, inner) {
+                let ptr = self.receiver_text(ptr);
+                return Some(if same_type {
+                    ptr
+                } else {
+                    format!("{} as {}", ptr, target)
+                });
+            }
+        }
+
+        // Pick a name for the closure's argument that isn't used in the body.
+        let body = strip(&cast.expr);
+        let body_span = body.span();
+        let body_text = self.text(body_span);
+        let var = ["a", "addr", "addr_"].iter().find(|name| {
+            !body_text
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|w| w == **name)
+        })?;
+
+        let body_start = self.offset(body_span.start());
+        let inner_start = self.offset(inner.span().start()) - body_start;
+        let inner_end = self.offset(inner.span().end()) - body_start;
+        let mut new_body = String::new();
+        new_body.push_str(&body_text[..inner_start]);
+        new_body.push_str(var);
+        new_body.push_str(&body_text[inner_end..]);
+
+        let mut suggestion = format!(
+            "{}.map_addr(|{}| {})",
+            self.receiver_text(ptr),
+            var,
+            new_body
+        );
+        if !same_type {
+            suggestion = format!("{} as {}", suggestion, target);
+        }
+        Some(suggestion)
+    }
+
+    /// Collects the `ptr as usize` casts in an integer expression. Returns `false` if
+    /// it also depends on a local computed from one (which we can't rewrite).
+    fn find_ptr_to_int<'e>(&self, expr: &'e Expr, out: &mut Vec<&'e syn::ExprCast>) -> bool {
+        match strip(expr) {
+            Expr::Path(_) => self.local(expr) != Some(Local::Addr),
+            Expr::Cast(cast) if self.is_ptr_to_int(expr) => {
+                out.push(cast);
+                true
+            }
+            Expr::Cast(cast) => self.find_ptr_to_int(&cast.expr, out),
+            Expr::Binary(bin) => {
+                self.find_ptr_to_int(&bin.left, out) && self.find_ptr_to_int(&bin.right, out)
+            }
+            Expr::Unary(unary) => self.find_ptr_to_int(&unary.expr, out),
+            Expr::MethodCall(call) => {
+                self.find_ptr_to_int(&call.receiver, out)
+                    && call.args.iter().all(|arg| self.find_ptr_to_int(arg, out))
+            }
+            _ => true,
+        }
+    }
+
+    /// Suggests `sptr::invalid::<T>(addr)` for `addr as *const T`.
+    fn suggest_invalid(&self, cast: &syn::ExprCast) -> Option<String> {
+        let ptr = match &*cast.ty {
+            Type::Ptr(ptr) => ptr,
+            _ => return None,
+        };
+        let func = if ptr.mutability.is_some() {
+            "invalid_mut"
+        } else {
+            "invalid"
+        };
+        let addr = self.text(strip(&cast.expr).span());
+        Some(match &*ptr.elem {
+            Type::Infer(_) => format!("sptr::{}({})", func, addr),
+            elem => format!("sptr::{}::<{}>({})", func, self.text(elem.span()), addr),
+        })
+    }
+
+    fn bind(&mut self, pat: &Pat, ty: Option<&Type>, init: Option<&Expr>) {
+        let ident = match pat {
+            Pat::Ident(pat) => pat.ident.to_string(),
+            Pat::Type(pat) => return self.bind(&pat.pat, Some(&pat.ty), init),
+            _ => return,
+        };
+        let is_ptr = ty.is_some_and(is_ptr_type) || init.is_some_and(|e| self.is_pointer(e));
+        let is_int = ty.is_some_and(is_int_type) || init.is_some_and(|e| self.is_int(e));
+        let is_addr = is_int && init.is_some_and(|e| self.contains_ptr_to_int(e));
+        let ptr_type = match (ty, init.map(strip)) {
+            (Some(ty), _) if is_ptr_type(ty) => Some(ty.span()),
+            (None, Some(Expr::Cast(cast))) if is_ptr_type(&cast.ty) => Some(cast.ty.span()),
+            _ => None,
+        }
+        .map(|span| self.text(span).to_owned());
+
+        let scope = self.scopes.last_mut().unwrap();
+        scope.pointers.remove(&ident);
+        scope.ints.remove(&ident);
+        scope.addrs.remove(&ident);
+        scope.pointer_types.remove(&ident);
+        if is_ptr {
+            if let Some(ptr_type) = ptr_type {
+                scope.pointer_types.insert(ident.clone(), ptr_type);
+            }
+            scope.pointers.insert(ident);
+        } else if is_addr {
+            scope.addrs.insert(ident);
+        } else if is_int {
+            scope.ints.insert(ident);
+        }
+    }
+
+    fn visit_cast(&mut self, cast: &'_ syn::ExprCast) {
+        if is_int_type(&cast.ty) && self.is_pointer(&cast.expr) {
+            let suggestion = self.suggest_addr(cast);
+            self.report(cast.span(), Fix::Addr, false, Some(suggestion));
+        } else if is_ptr_type(&cast.ty) && self.is_int(&cast.expr) {
+            if self.contains_ptr_to_int(&cast.expr) {
+                // Report the whole round-trip once, instead of each half.
+                let suggestion = self.suggest_map_addr(cast);
+                self.report(cast.span(), Fix::WithAddr, false, suggestion);
+                return self.visit_round_trip(&cast.expr);
+            } else if is_int_literal(&cast.expr) {
+                let suggestion = self.suggest_invalid(cast);
+                self.report(cast.span(), Fix::Invalid, false, suggestion);
+            } else {
+                self.report(cast.span(), Fix::ExposeAddr, false, None);
+            }
+        }
+        visit::visit_expr_cast(self, cast);
+    }
+
+    /// Visits the integer half of a round-trip, skipping the `ptr as usize` casts.
+    fn visit_round_trip(&mut self, expr: &'_ Expr) {
+        if self.is_ptr_to_int(expr) {
+            if let Expr::Cast(cast) = strip(expr) {
+                self.visit_expr(&cast.expr);
+            }
+            return;
+        }
+        match strip(expr) {
+            Expr::Cast(cast) => self.visit_round_trip(&cast.expr),
+            Expr::Binary(bin) => {
+                self.visit_round_trip(&bin.left);
+                self.visit_round_trip(&bin.right);
+            }
+            Expr::Unary(unary) => self.visit_round_trip(&unary.expr),
+            Expr::MethodCall(call) => {
+                self.visit_round_trip(&call.receiver);
+                for arg in &call.args {
+                    self.visit_round_trip(arg);
+                }
+            }
+            expr => self.visit_expr(expr),
+        }
+    }
+
+    fn visit_transmute(&mut self, call: &'_ syn::ExprCall) {
+        let is_transmute = match strip(&call.func) {
+            Expr::Path(path) => last_segment_is(&path.path, &["transmute", "transmute_copy"]),
+            _ => false,
+        };
+        if !is_transmute || call.args.len() != 1 {
+            return;
+        }
+        let arg = &call.args[0];
+        let fix = match transmute_types(call) {
+            Some((src, dst)) if is_ptr_type(src) && is_int_type(dst) => Some(Fix::Addr),
+            Some((src, dst)) if is_int_type(src) && is_ptr_type(dst) => {
+                if is_int_literal(arg) {
+                    Some(Fix::Invalid)
+                } else {
+                    Some(Fix::WithAddr)
+                }
+            }
+            Some(_) => None,
+            None if self.is_pointer(arg) => Some(Fix::Addr),
+            None if is_int_literal(arg) => Some(Fix::Invalid),
+            None if self.is_int(arg) => Some(Fix::WithAddr),
+            None => None,
+        };
+        if let Some(fix) = fix {
+            self.report(call.span(), fix, true, None);
+        }
+    }
+}
+
+impl<'ast, 'src> Visit<'ast> for Auditor<'src> {
+    fn visit_block(&mut self, block: &'ast syn::Block) {
+        self.scopes.push(Scope::default());
+        visit::visit_block(self, block);
+        self.scopes.pop();
+    }
+
+    fn visit_signature(&mut self, sig: &'ast syn::Signature) {
+        // The body's block will push its own scope on top of this one, which is popped
+        // by whoever visits the fn (see visit_item_fn and friends).
+        for input in &sig.inputs {
+            if let syn::FnArg::Typed(arg) = input {
+                self.bind(&arg.pat, Some(&arg.ty), None);
+            }
+        }
+        visit::visit_signature(self, sig);
+    }
+
+    fn visit_item_fn(&mut self, item: &'ast syn::ItemFn) {
+        self.scopes.push(Scope::default());
+        visit::visit_item_fn(self, item);
+        self.scopes.pop();
+    }
+
+    fn visit_impl_item_fn(&mut self, item: &'ast syn::ImplItemFn) {
+        self.scopes.push(Scope::default());
+        visit::visit_impl_item_fn(self, item);
+        self.scopes.pop();
+    }
+
+    fn visit_trait_item_fn(&mut self, item: &'ast syn::TraitItemFn) {
+        self.scopes.push(Scope::default());
+        visit::visit_trait_item_fn(self, item);
+        self.scopes.pop();
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        // Visit the initializer first, since the binding isn't in scope for it.
+        let init = local.init.as_ref().map(|init| &*init.expr);
+        if let Some(init) = &local.init {
+            self.visit_expr(&init.expr);
+            if let Some((_, diverge)) = &init.diverge {
+                self.visit_expr(diverge);
+            }
+        }
+        self.bind(&local.pat, None, init);
+    }
+
+    fn visit_expr_cast(&mut self, cast: &'ast syn::ExprCast) {
+        self.visit_cast(cast);
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+        self.visit_transmute(call);
+        visit::visit_expr_call(self, call);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{audit_source, fix_source, Fix};
+
+    fn fixes(body: &str) -> Vec<(Fix, String)> {
+        let src = format!("fn f(p: *const u8, len: u32, r: &u8) {{\n{}\n}}", body);
+        audit_source(&src)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.fix, f.snippet))
+            .collect()
+    }
+
+    #[test]
+    fn test_addr() {
+        assert_eq!(
+            fixes("let x = p as usize;"),
+            [(Fix::Addr, "p as usize".into())]
+        );
+        assert_eq!(
+            fixes("let x = r as *const u8 as usize;"),
+            [(Fix::Addr, "r as *const u8 as usize".into())]
+        );
+        assert_eq!(
+            fixes("let x = v.as_ptr() as u64;"),
+            [(Fix::Addr, "v.as_ptr() as u64".into())]
+        );
+        assert_eq!(
+            fixes("let q = p.add(1); let x = q as isize;"),
+            [(Fix::Addr, "q as isize".into())]
+        );
+    }
+
+    #[test]
+    fn test_with_addr() {
+        assert_eq!(
+            fixes("let q = (p as usize + 4) as *mut u8;"),
+            [(Fix::WithAddr, "(p as usize + 4) as *mut u8".into())]
+        );
+        assert_eq!(
+            fixes("let q = ((p as usize) & !7) as *const u8;"),
+            [(Fix::WithAddr, "((p as usize) & !7) as *const u8".into())]
+        );
+        assert_eq!(
+            fixes("let a = p as usize; let q = (a + 1) as *const u8;"),
+            [
+                (Fix::Addr, "p as usize".into()),
+                (Fix::WithAddr, "(a + 1) as *const u8".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_and_expose() {
+        assert_eq!(
+            fixes("let q = 0x1000 as *const u32;"),
+            [(Fix::Invalid, "0x1000 as *const u32".into())]
+        );
+        assert_eq!(
+            fixes("let q = (1 << 12) as *mut u32;"),
+            [(Fix::Invalid, "(1 << 12) as *mut u32".into())]
+        );
+        assert_eq!(
+            fixes("let a: usize = get(); let q = a as *mut u32;"),
+            [(Fix::ExposeAddr, "a as *mut u32".into())]
+        );
+    }
+
+    #[test]
+    fn test_transmute() {
+        assert_eq!(
+            fixes("let x: usize = unsafe { core::mem::transmute(p) };"),
+            [(Fix::Addr, "core::mem::transmute(p)".into())]
+        );
+        assert_eq!(
+            fixes("let x = unsafe { std::mem::transmute::<usize, *const u8>(a) };"),
+            [(
+                Fix::WithAddr,
+                "std::mem::transmute::<usize, *const u8>(a)".into()
+            )]
+        );
+        assert_eq!(
+            fixes("let x: *const u8 = unsafe { transmute(8usize) };"),
+            [(Fix::Invalid, "transmute(8usize)".into())]
+        );
+        assert!(fixes("let x: [u8; 4] = unsafe { transmute(y) };").is_empty());
+    }
+
+    #[test]
+    fn test_not_reported() {
+        assert!(fixes("let x = len as usize;").is_empty());
+        assert!(fixes("let q = r as *const u8;").is_empty());
+        assert!(fixes("let q = p as *mut u8;").is_empty());
+        assert!(fixes("let x = y.wrapping_add(1) as usize;").is_empty());
+        // Shadowing a pointer with an int.
+        assert!(fixes("let p = 5u32; let x = p as usize;").is_empty());
+    }
+
+    #[test]
+    fn test_positions() {
+        let src = "fn f(p: *const u8) {\n    let x =  p as usize;\n}\n";
+        let findings = audit_source(src).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].line, findings[0].column), (2, 14));
+        assert_eq!((findings[0].end_line, findings[0].end_column), (2, 24));
+    }
+
+    fn fixed(body: &str) -> String {
+        let src = format!("fn f(p: *const u8, q: *mut u8) {{\n    {}\n}}\n", body);
+        let out = fix_source(&src).unwrap();
+        let start = out.find(") {\n").unwrap() + 4;
+        out[start..out.len() - 3].trim().to_owned()
+    }
+
+    #[test]
+    fn test_fix_addr() {
+        assert_eq!(fixed("let x = p as usize;"), "let x = p.addr();");
+        assert_eq!(
+            fixed("let x = p.add(1) as u64;"),
+            "let x = p.add(1).addr() as u64;"
+        );
+        assert_eq!(
+            fixed("let x = &y as *const u8 as usize;"),
+            "let x = (&y as *const u8).addr();"
+        );
+    }
+
+    #[test]
+    fn test_fix_map_addr() {
+        assert_eq!(
+            fixed("let r = (q as usize + 4) as *mut u8;"),
+            "let r = q.map_addr(|a| a + 4);"
+        );
+        assert_eq!(
+            fixed("let r = ((p as usize) & !7) as *mut u8;"),
+            "let r = p.map_addr(|a| (a) & !7) as *mut u8;"
+        );
+        assert_eq!(
+            fixed("let r = (p as usize + a) as *const u8;"),
+            "let r = p.map_addr(|addr| addr + a);"
+        );
+        // No arithmetic, no map_addr.
+        assert_eq!(fixed("let r = (p as usize) as *const u8;"), "let r = p;");
+        assert_eq!(
+            fixed("let r = (p as usize) as *mut u8;"),
+            "let r = p as *mut u8;"
+        );
+    }
+
+    #[test]
+    fn test_fix_invalid() {
+        assert_eq!(
+            fixed("let r = 0x1000 as *const u32;"),
+            "let r = sptr::invalid::<u32>(0x1000);"
+        );
+        assert_eq!(
+            fixed("let r: *mut u8 = 8 as *mut _;"),
+            "let r: *mut u8 = sptr::invalid_mut(8);"
+        );
+    }
+
+    #[test]
+    fn test_fix_todo() {
+        assert_eq!(
+            fixed("let n: usize = get();\n    let r = n as *mut u8;"),
+            "let n: usize = get();\n    \
+             // TODO(sptr-fix): needs expose_addr: `n as *mut u8`\n    let r = n as *mut u8;"
+        );
+        // Round-trips through a local need a human to decide which pointer to use.
+        assert_eq!(
+            fixed("let a = p as usize; let r = (a + 1) as *const u8;"),
+            "// TODO(sptr-fix): needs with_addr: `(a + 1) as *const u8`\n    \
+             let a = p.addr(); let r = (a + 1) as *const u8;"
+        );
+        // The comment goes above the whole statement, not inside it.
+        assert_eq!(
+            fixed("let n: usize = get();\n    let r = call(\n        n as *mut u8,\n    );"),
+            "let n: usize = get();\n    \
+             // TODO(sptr-fix): needs expose_addr: `n as *mut u8`\n    \
+             let r = call(\n        n as *mut u8,\n    );"
+        );
+        // And never inside a string literal.
+        let body = "let n: usize = get();\n    let s = \"a\nb\"; let r = n as *mut u8;";
+        assert_eq!(fixed(body), body);
+        let body = "let n: usize = get();\n    let s = r#\"a\nb\"#; let r = n as *mut u8;";
+        assert_eq!(fixed(body), body);
+    }
+
+    #[test]
+    fn test_fix_header() {
+        let src = "//! Docs.\n#![no_std]\n\nfn f(p: *const u8) -> usize {\n    p as usize\n}\n";
+        let out = fix_source(src).unwrap();
+        assert_eq!(
+            out,
+            "//! Docs.\n#![no_std]\n\n#![allow(unstable_name_collisions)]\nuse sptr::Strict;\n\n\
+             fn f(p: *const u8) -> usize {\n    p.addr()\n}\n"
+        );
+        // Running it again changes nothing.
+        assert_eq!(fix_source(&out).unwrap(), out);
+
+        // Only invalid() doesn't need the trait.
+        let src = "fn f() -> *const u8 {\n    1 as *const u8\n}\n";
+        assert!(!fix_source(src).unwrap().contains("use sptr::Strict"));
+    }
+}
@@ -0,0 +1,97 @@
+//! `sptr-audit [PATH]...`
+//!
+//! Reports every provenance-breaking pointer<->integer cast in the Rust sources under the
+//! given files or directories (default: the current directory), one per line as
+//! `file:line:column: <fix>: <cast>`.
+//!
+//! Exits with 0 if nothing was found, 1 if any casts were found, and 2 if some files
+//! couldn't be read or parsed, so it can be used to gate CI.
+
+use std::path::PathBuf;
+use std::process;
+
+fn usage() -> ! {
+    eprintln!("usage: sptr-audit [PATH]...");
+    eprintln!();
+    eprintln!("Reports pointer<->integer casts that should be migrated to sptr's Strict APIs.");
+    process::exit(2);
+}
+
+fn main() {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for arg in std::env::args_os().skip(1) {
+        if arg == "-h" || arg == "--help" {
+            usage();
+        }
+        roots.push(arg.into());
+    }
+    if roots.is_empty() {
+        roots.push(".".into());
+    }
+
+    let mut failed = false;
+    let mut files = Vec::new();
+    for root in &roots {
+        if let Err(err) = sptr_audit::collect_sources(root, &mut files) {
+            eprintln!("{}: {}", root.display(), err);
+            failed = true;
+        }
+    }
+
+    let mut total = 0;
+    for file in &files {
+        let src = match std::fs::read_to_string(file) {
+            Ok(src) => src,
+            Err(err) => {
+                eprintln!("{}: {}", file.display(), err);
+                failed = true;
+                continue;
+            }
+        };
+        let findings = match sptr_audit::audit_source(&src) {
+            Ok(findings) => findings,
+            Err(err) => {
+                let start = err.span().start();
+                eprintln!(
+                    "{}:{}:{}: parse error: {}",
+                    file.display(),
+                    start.line,
+                    start.column + 1,
+                    err
+                );
+                failed = true;
+                continue;
+            }
+        };
+        for finding in &findings {
+            let what = if finding.transmute {
+                " (transmute)"
+            } else {
+                ""
+            };
+            println!(
+                "{}:{}:{}: {}{}: {}",
+                file.display(),
+                finding.line,
+                finding.column,
+                finding.fix,
+                what,
+                finding.snippet
+            );
+        }
+        total += findings.len();
+    }
+
+    eprintln!(
+        "sptr-audit: {} provenance-breaking cast{} in {} file{}",
+        total,
+        if total == 1 { "" } else { "s" },
+        files.len(),
+        if files.len() == 1 { "" } else { "s" },
+    );
+    if failed {
+        process::exit(2);
+    } else if total > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,732 @@
+//! Atomic pointers with extra data -- [AtomicTaggedPtr][] and [AtomicPtrWithCounter][].
+//!
+//! Lock-free data structures often need to update a pointer and some extra data (like a
+//! generation counter, to avoid the ABA problem) in a single atomic operation. The usual
+//! trick is to pack both into an `AtomicUsize` (or an `AtomicU128`), which loses the
+//! pointer's provenance. [`AtomicTaggedPtr`] uses an `AtomicPtr` instead, and packs and
+//! unpacks the extra data with [`map_addr`][crate::Strict::map_addr].
+//! [`AtomicPtrWithCounter`] needs a whole word for the counter, so it stores the pointer
+//! and the counter side by side, and keeps the pointer half a real pointer all the way into
+//! the double-width compare-exchange instruction.
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// An `AtomicPtr` with a `BITS`-bit tag stored in its alignment bits.
+///
+/// `T` must be aligned to at least `1 << BITS` bytes (this is checked when it's created), so
+/// that those bits of the address are always 0. Tags are truncated to `BITS` bits when
+/// they're stored, so a counter kept in the tag just wraps around.
+///
+/// All the operations work on `(pointer, tag)` pairs, and compare-exchanges compare both.
+///
+/// Requires const generics (1.51).
+///
+/// ```
+/// use sptr::AtomicTaggedPtr;
+/// use std::sync::atomic::Ordering;
+///
+/// let mut node = 5u64;
+/// let head = AtomicTaggedPtr::<u64, 3>::new(&mut node, 0);
+///
+/// // Bump the generation counter without touching the pointer.
+/// head.fetch_update_tag(Ordering::AcqRel, Ordering::Acquire, |tag| Some(tag + 1)).unwrap();
+/// let (ptr, tag) = head.load(Ordering::Acquire);
+/// assert_eq!((unsafe { *ptr }, tag), (5, 1));
+///
+/// // A stale generation makes the compare-exchange fail.
+/// let stale = head.compare_exchange((ptr, 0), (ptr, 2), Ordering::AcqRel, Ordering::Acquire);
+/// assert_eq!(stale, Err((ptr, 1)));
+/// ```
+pub struct AtomicTaggedPtr<T, const BITS: u32> {
+    inner: AtomicPtr<T>,
+}
+
+impl<T, const BITS: u32> AtomicTaggedPtr<T, BITS> {
+    /// The mask of the bits of the address that hold the tag.
+    pub const TAG_MASK: usize = (1 << BITS) - 1;
+
+    /// Creates a new `AtomicTaggedPtr`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` isn't aligned to at least `1 << BITS` bytes.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn new(ptr: *mut T, tag: usize) -> Self {
+        assert!(
+            core::mem::align_of::<T>() > Self::TAG_MASK,
+            "AtomicTaggedPtr needs {} alignment bits, but {} only has {}",
+            BITS,
+            core::any::type_name::<T>(),
+            core::mem::align_of::<T>().trailing_zeros()
+        );
+        AtomicTaggedPtr {
+            inner: AtomicPtr::new(Self::pack(ptr, tag)),
+        }
+    }
+
+    /// Consumes the atomic and returns the contained pointer and tag.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> (*mut T, usize) {
+        Self::unpack(self.inner.into_inner())
+    }
+
+    /// Loads the pointer and tag.
+    #[inline]
+    #[must_use]
+    pub fn load(&self, order: Ordering) -> (*mut T, usize) {
+        Self::unpack(self.inner.load(order))
+    }
+
+    /// Stores a pointer and tag.
+    #[inline]
+    pub fn store(&self, ptr: *mut T, tag: usize, order: Ordering) {
+        self.inner.store(Self::pack(ptr, tag), order);
+    }
+
+    /// Stores a pointer and tag, returning the previous ones.
+    #[inline]
+    pub fn swap(&self, ptr: *mut T, tag: usize, order: Ordering) -> (*mut T, usize) {
+        Self::unpack(self.inner.swap(Self::pack(ptr, tag), order))
+    }
+
+    /// Stores `new` if the current pointer and tag are both equal to `current`.
+    ///
+    /// Returns the previous pointer and tag, in `Ok` if the exchange happened and `Err`
+    /// otherwise. See `AtomicPtr::compare_exchange` for the meaning of the orderings.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.inner
+            .compare_exchange(
+                Self::pack(current.0, current.1),
+                Self::pack(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Self::unpack)
+            .map_err(Self::unpack)
+    }
+
+    /// Like [`compare_exchange`][AtomicTaggedPtr::compare_exchange], but allowed to
+    /// spuriously fail, which can be more efficient in a loop.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        self.inner
+            .compare_exchange_weak(
+                Self::pack(current.0, current.1),
+                Self::pack(new.0, new.1),
+                success,
+                failure,
+            )
+            .map(Self::unpack)
+            .map_err(Self::unpack)
+    }
+
+    /// Atomically updates the tag with `f`, keeping the pointer the same.
+    ///
+    /// `f` may be called several times if other threads change the value in the meantime.
+    /// If it returns `None`, the update is abandoned. Returns the previous pointer and tag,
+    /// in `Ok` if the update happened and `Err` otherwise.
+    #[inline]
+    pub fn fetch_update_tag<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<(*mut T, usize), (*mut T, usize)>
+    where
+        F: FnMut(usize) -> Option<usize>,
+    {
+        let mut prev = self.inner.load(fetch_order);
+        loop {
+            let (ptr, tag) = Self::unpack(prev);
+            let new_tag = match f(tag) {
+                Some(new_tag) => new_tag,
+                None => return Err((ptr, tag)),
+            };
+            match self.inner.compare_exchange_weak(
+                prev,
+                Self::pack(ptr, new_tag),
+                set_order,
+                fetch_order,
+            ) {
+                Ok(_) => return Ok((ptr, tag)),
+                Err(next) => prev = next,
+            }
+        }
+    }
+
+    fn pack(ptr: *mut T, tag: usize) -> *mut T {
+        debug_assert!(
+            ptr.addr() & Self::TAG_MASK == 0,
+            "{:p} isn't aligned enough to be tagged",
+            ptr
+        );
+        ptr.map_addr(|a| a | (tag & Self::TAG_MASK))
+    }
+
+    fn unpack(packed: *mut T) -> (*mut T, usize) {
+        (
+            packed.map_addr(|a| a & !Self::TAG_MASK),
+            packed.addr() & Self::TAG_MASK,
+        )
+    }
+}
+
+/// A null pointer with the tag 0.
+impl<T, const BITS: u32> Default for AtomicTaggedPtr<T, BITS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(core::ptr::null_mut(), 0)
+    }
+}
+
+impl<T, const BITS: u32> core::fmt::Debug for AtomicTaggedPtr<T, BITS> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (ptr, tag) = self.load(Ordering::Relaxed);
+        f.debug_struct("AtomicTaggedPtr")
+            .field("ptr", &ptr)
+            .field("tag", &tag)
+            .finish()
+    }
+}
+
+/// An atomic pointer and a word-sized counter, updated together.
+///
+/// This uses a double-width compare-exchange where the target has one: `cmpxchg16b` on
+/// x86_64 (which needs `-C target-feature=+cmpxchg16b`) and `casp` on AArch64 (which needs
+/// `-C target-feature=+lse`). The pointer is passed to the instruction as a pointer rather
+/// than as half of a `u128`, so it keeps its provenance. Everywhere else, including 32-bit
+/// targets, this falls back to a spin lock, which [`IS_LOCK_FREE`][Self::IS_LOCK_FREE]
+/// tells you about.
+///
+/// Compare-exchanges compare both the pointer and the counter. The orderings passed in are
+/// a minimum: `cmpxchg16b` is always `SeqCst`, and the lock's acquire and release can't be
+/// weaker than `Acquire` and `Release`.
+///
+/// There's no double-width atomic load, so with the native implementations
+/// [`load`][Self::load] is a compare-exchange that writes back the value it reads. That means
+/// it needs the memory to be writable, even though it doesn't change it: an
+/// `AtomicPtrWithCounter` in a read-only mapping can't be loaded from.
+///
+/// The native implementations require inline assembly (1.59).
+///
+/// ```
+/// use sptr::AtomicPtrWithCounter;
+/// use std::sync::atomic::Ordering;
+///
+/// let mut node = 5u64;
+/// let head = AtomicPtrWithCounter::new(&mut node, 0);
+///
+/// // Bump the generation counter without touching the pointer.
+/// head.fetch_update(Ordering::AcqRel, Ordering::Acquire, |(ptr, n)| Some((ptr, n + 1)))
+///     .unwrap();
+/// let (ptr, counter) = head.load(Ordering::Acquire);
+/// assert_eq!((unsafe { *ptr }, counter), (5, 1));
+///
+/// // A stale generation makes the compare-exchange fail.
+/// let stale = head.compare_exchange((ptr, 0), (ptr, 2), Ordering::AcqRel, Ordering::Acquire);
+/// assert_eq!(stale, Err((ptr, 1)));
+/// ```
+#[repr(C, align(16))]
+pub struct AtomicPtrWithCounter<T> {
+    pair: UnsafeCell<Pair<T>>,
+    lock: imp::Lock,
+}
+
+// Like `AtomicPtr`, this only ever hands out copies of the pointer.
+unsafe impl<T> Send for AtomicPtrWithCounter<T> {}
+unsafe impl<T> Sync for AtomicPtrWithCounter<T> {}
+
+/// The two halves, in the order the double-width instructions expect them.
+#[repr(C)]
+struct Pair<T> {
+    ptr: *mut T,
+    counter: usize,
+}
+
+impl<T> Copy for Pair<T> {}
+impl<T> Clone for Pair<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for Pair<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr && self.counter == other.counter
+    }
+}
+
+impl<T> AtomicPtrWithCounter<T> {
+    /// Whether the operations use a double-width compare-exchange rather than a lock.
+    pub const IS_LOCK_FREE: bool = imp::IS_LOCK_FREE;
+
+    /// Creates a new `AtomicPtrWithCounter`.
+    #[inline]
+    #[must_use]
+    pub fn new(ptr: *mut T, counter: usize) -> Self {
+        AtomicPtrWithCounter {
+            pair: UnsafeCell::new(Pair { ptr, counter }),
+            lock: imp::Lock::new(),
+        }
+    }
+
+    /// Consumes the atomic and returns the contained pointer and counter.
+    #[inline]
+    #[must_use]
+    pub fn into_inner(self) -> (*mut T, usize) {
+        let pair = self.pair.into_inner();
+        (pair.ptr, pair.counter)
+    }
+
+    /// Loads the pointer and counter.
+    ///
+    /// With the native implementations, this writes to the atomic (see the
+    /// [type docs][AtomicPtrWithCounter]).
+    #[inline]
+    #[must_use]
+    pub fn load(&self, order: Ordering) -> (*mut T, usize) {
+        // SAFETY: `pair` is 16-aligned (thanks to the `repr`), and is only accessed through
+        // `imp` while shared.
+        let pair = unsafe { imp::load(self.pair.get(), &self.lock, order) };
+        (pair.ptr, pair.counter)
+    }
+
+    /// Stores a pointer and counter.
+    #[inline]
+    pub fn store(&self, ptr: *mut T, counter: usize, order: Ordering) {
+        let _ = self.swap(ptr, counter, order);
+    }
+
+    /// Stores a pointer and counter, returning the previous ones.
+    #[inline]
+    pub fn swap(&self, ptr: *mut T, counter: usize, order: Ordering) -> (*mut T, usize) {
+        match self.fetch_update(order, order, |_| Some((ptr, counter))) {
+            Ok(prev) | Err(prev) => prev,
+        }
+    }
+
+    /// Stores `new` if the current pointer and counter are both equal to `current`.
+    ///
+    /// Returns the previous pointer and counter, in `Ok` if the exchange happened and `Err`
+    /// otherwise.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: (*mut T, usize),
+        new: (*mut T, usize),
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<(*mut T, usize), (*mut T, usize)> {
+        let current = Pair {
+            ptr: current.0,
+            counter: current.1,
+        };
+        let new = Pair {
+            ptr: new.0,
+            counter: new.1,
+        };
+        let dst = self.pair.get();
+        // SAFETY: as in `load`.
+        unsafe { imp::compare_exchange(dst, current, new, &self.lock, success, failure) }
+            .map(|pair| (pair.ptr, pair.counter))
+            .map_err(|pair| (pair.ptr, pair.counter))
+    }
+
+    /// Atomically updates the pointer and counter with `f`.
+    ///
+    /// `f` may be called several times if other threads change the value in the meantime.
+    /// If it returns `None`, the update is abandoned. Returns the previous pointer and
+    /// counter, in `Ok` if the update happened and `Err` otherwise.
+    #[inline]
+    pub fn fetch_update<F>(
+        &self,
+        set_order: Ordering,
+        fetch_order: Ordering,
+        mut f: F,
+    ) -> Result<(*mut T, usize), (*mut T, usize)>
+    where
+        F: FnMut((*mut T, usize)) -> Option<(*mut T, usize)>,
+    {
+        let mut prev = self.load(fetch_order);
+        while let Some(new) = f(prev) {
+            match self.compare_exchange(prev, new, set_order, fetch_order) {
+                Ok(prev) => return Ok(prev),
+                Err(next) => prev = next,
+            }
+        }
+        Err(prev)
+    }
+}
+
+/// A null pointer with the counter 0.
+impl<T> Default for AtomicPtrWithCounter<T> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(core::ptr::null_mut(), 0)
+    }
+}
+
+impl<T> core::fmt::Debug for AtomicPtrWithCounter<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (ptr, counter) = self.load(Ordering::Relaxed);
+        f.debug_struct("AtomicPtrWithCounter")
+            .field("ptr", &ptr)
+            .field("counter", &counter)
+            .finish()
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "cmpxchg16b"))]
+mod imp {
+    use super::Pair;
+    use core::sync::atomic::Ordering;
+
+    pub(super) const IS_LOCK_FREE: bool = true;
+
+    pub(super) struct Lock;
+
+    impl Lock {
+        #[inline]
+        pub(super) fn new() -> Self {
+            Lock
+        }
+    }
+
+    /// `dst` must be valid for reads and writes, and 16-aligned.
+    #[inline]
+    pub(super) unsafe fn load<T>(dst: *mut Pair<T>, lock: &Lock, order: Ordering) -> Pair<T> {
+        // A compare-exchange that stores back whatever it finds.
+        let guess = Pair {
+            ptr: core::ptr::null_mut(),
+            counter: 0,
+        };
+        match compare_exchange(dst, guess, guess, lock, order, order) {
+            Ok(pair) | Err(pair) => pair,
+        }
+    }
+
+    /// `dst` must be valid for reads and writes, and 16-aligned.
+    ///
+    /// `lock cmpxchg16b` is `SeqCst`, so the orderings don't matter.
+    #[inline]
+    pub(super) unsafe fn compare_exchange<T>(
+        dst: *mut Pair<T>,
+        current: Pair<T>,
+        new: Pair<T>,
+        _lock: &Lock,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<Pair<T>, Pair<T>> {
+        let prev_ptr: *mut T;
+        let prev_counter: usize;
+        // rbx can't be named as an operand, so the new pointer is swapped in and out of
+        // it. `dst` is pinned to rdi, since a `reg` operand could be allocated to rbx.
+        core::arch::asm!(
+            "xchg {new_ptr}, rbx",
+            "lock cmpxchg16b xmmword ptr [rdi]",
+            "mov rbx, {new_ptr}",
+            in("rdi") dst,
+            new_ptr = inout(reg) new.ptr => _,
+            in("rcx") new.counter,
+            inout("rax") current.ptr => prev_ptr,
+            inout("rdx") current.counter => prev_counter,
+            options(nostack),
+        );
+        let prev = Pair {
+            ptr: prev_ptr,
+            counter: prev_counter,
+        };
+        if prev == current {
+            Ok(prev)
+        } else {
+            Err(prev)
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "lse"))]
+mod imp {
+    use super::Pair;
+    use core::sync::atomic::Ordering;
+
+    pub(super) const IS_LOCK_FREE: bool = true;
+
+    pub(super) struct Lock;
+
+    impl Lock {
+        #[inline]
+        pub(super) fn new() -> Self {
+            Lock
+        }
+    }
+
+    /// `dst` must be valid for reads and writes, and 16-aligned.
+    #[inline]
+    pub(super) unsafe fn load<T>(dst: *mut Pair<T>, lock: &Lock, order: Ordering) -> Pair<T> {
+        // A compare-exchange that stores back whatever it finds.
+        let guess = Pair {
+            ptr: core::ptr::null_mut(),
+            counter: 0,
+        };
+        match compare_exchange(dst, guess, guess, lock, order, order) {
+            Ok(pair) | Err(pair) => pair,
+        }
+    }
+
+    /// `dst` must be valid for reads and writes, and 16-aligned.
+    #[inline]
+    pub(super) unsafe fn compare_exchange<T>(
+        dst: *mut Pair<T>,
+        current: Pair<T>,
+        new: Pair<T>,
+        _lock: &Lock,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Pair<T>, Pair<T>> {
+        use Ordering::*;
+
+        let prev_ptr: *mut T;
+        let prev_counter: usize;
+        // The lower register of each pair goes with the lower address.
+        macro_rules! casp {
+            ($insn:literal) => {
+                core::arch::asm!(
+                    $insn,
+                    dst = in(reg) dst,
+                    inout("x0") current.ptr => prev_ptr,
+                    inout("x1") current.counter => prev_counter,
+                    in("x2") new.ptr,
+                    in("x3") new.counter,
+                    options(nostack),
+                )
+            };
+        }
+        // The failure ordering only needs the acquire half, since nothing is written.
+        let acquire = matches!(success, Acquire | AcqRel | SeqCst)
+            || matches!(failure, Acquire | AcqRel | SeqCst);
+        let release = matches!(success, Release | AcqRel | SeqCst);
+        match (acquire, release) {
+            (false, false) => casp!("casp x0, x1, x2, x3, [{dst}]"),
+            (true, false) => casp!("caspa x0, x1, x2, x3, [{dst}]"),
+            (false, true) => casp!("caspl x0, x1, x2, x3, [{dst}]"),
+            (true, true) => casp!("caspal x0, x1, x2, x3, [{dst}]"),
+        }
+        let prev = Pair {
+            ptr: prev_ptr,
+            counter: prev_counter,
+        };
+        if prev == current {
+            Ok(prev)
+        } else {
+            Err(prev)
+        }
+    }
+}
+
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_feature = "cmpxchg16b"),
+    all(target_arch = "aarch64", target_feature = "lse")
+)))]
+mod imp {
+    use super::Pair;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    pub(super) const IS_LOCK_FREE: bool = false;
+
+    pub(super) struct Lock(AtomicBool);
+
+    impl Lock {
+        #[inline]
+        pub(super) fn new() -> Self {
+            Lock(AtomicBool::new(false))
+        }
+    }
+
+    impl Lock {
+        /// Takes the lock, which is always at least `Acquire`.
+        #[inline]
+        fn lock(&self, order: Ordering) {
+            let order = match order {
+                Ordering::SeqCst => Ordering::SeqCst,
+                _ => Ordering::Acquire,
+            };
+            while self
+                .0
+                .compare_exchange_weak(false, true, order, Ordering::Relaxed)
+                .is_err()
+            {
+                core::hint::spin_loop();
+            }
+        }
+
+        /// Releases the lock, which is always at least `Release`.
+        #[inline]
+        fn unlock(&self, order: Ordering) {
+            let order = match order {
+                Ordering::SeqCst => Ordering::SeqCst,
+                _ => Ordering::Release,
+            };
+            self.0.store(false, order);
+        }
+    }
+
+    /// `dst` must be valid for reads, and only accessed while holding `lock`.
+    #[inline]
+    pub(super) unsafe fn load<T>(dst: *mut Pair<T>, lock: &Lock, order: Ordering) -> Pair<T> {
+        lock.lock(order);
+        let pair = dst.read();
+        lock.unlock(order);
+        pair
+    }
+
+    /// `dst` must be valid for reads and writes, and only accessed while holding `lock`.
+    #[inline]
+    pub(super) unsafe fn compare_exchange<T>(
+        dst: *mut Pair<T>,
+        current: Pair<T>,
+        new: Pair<T>,
+        lock: &Lock,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Pair<T>, Pair<T>> {
+        // We don't know which ordering applies until we've looked, so take the stronger one.
+        let order = if success == Ordering::SeqCst || failure == Ordering::SeqCst {
+            Ordering::SeqCst
+        } else {
+            success
+        };
+        lock.lock(order);
+        let prev = dst.read();
+        let result = if prev == current {
+            dst.write(new);
+            Ok(prev)
+        } else {
+            Err(prev)
+        };
+        lock.unlock(order);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AtomicPtrWithCounter, AtomicTaggedPtr};
+    use core::sync::atomic::Ordering::*;
+
+    #[test]
+    fn test_tagged() {
+        let mut values = [1u32, 2];
+        let (a, b) = (&mut values[0] as *mut u32, &mut values[1] as *mut u32);
+        let atomic = AtomicTaggedPtr::<u32, 2>::new(a, 3);
+        assert_eq!(atomic.load(SeqCst), (a, 3));
+
+        assert_eq!(
+            atomic.compare_exchange((a, 2), (b, 0), SeqCst, SeqCst),
+            Err((a, 3))
+        );
+        assert_eq!(
+            atomic.compare_exchange((a, 3), (b, 0), SeqCst, SeqCst),
+            Ok((a, 3))
+        );
+        assert_eq!(atomic.swap(a, 1, SeqCst), (b, 0));
+
+        // Tags wrap around.
+        for _ in 0..3 {
+            let _ = atomic.fetch_update_tag(SeqCst, SeqCst, |tag| Some(tag + 1));
+        }
+        assert_eq!(atomic.load(SeqCst), (a, 0));
+        assert_eq!(
+            atomic.fetch_update_tag(SeqCst, SeqCst, |_| None),
+            Err((a, 0))
+        );
+
+        atomic.store(b, 2, SeqCst);
+        let (ptr, tag) = atomic.into_inner();
+        assert_eq!(tag, 2);
+        unsafe { *ptr += 10 };
+        assert_eq!(values, [1, 12]);
+    }
+
+    #[test]
+    #[should_panic(expected = "alignment bits")]
+    fn test_underaligned_panics() {
+        let _ = AtomicTaggedPtr::<u16, 2>::default();
+    }
+
+    #[test]
+    fn test_with_counter() {
+        let mut values = [1u32, 2];
+        let (a, b) = (&mut values[0] as *mut u32, &mut values[1] as *mut u32);
+        let atomic = AtomicPtrWithCounter::new(a, !0);
+        assert_eq!(atomic.load(SeqCst), (a, !0));
+
+        assert_eq!(
+            atomic.compare_exchange((b, !0), (b, 0), SeqCst, SeqCst),
+            Err((a, !0))
+        );
+        assert_eq!(
+            atomic.compare_exchange((a, 0), (b, 0), SeqCst, SeqCst),
+            Err((a, !0))
+        );
+        assert_eq!(
+            atomic.compare_exchange((a, !0), (b, 0), SeqCst, SeqCst),
+            Ok((a, !0))
+        );
+        assert_eq!(atomic.swap(a, 1, SeqCst), (b, 0));
+        assert_eq!(atomic.fetch_update(SeqCst, SeqCst, |_| None), Err((a, 1)));
+
+        atomic.store(b, 2, SeqCst);
+        let (ptr, counter) = atomic.into_inner();
+        assert_eq!(counter, 2);
+        unsafe { *ptr += 10 };
+        assert_eq!(values, [1, 12]);
+    }
+
+    #[test]
+    fn test_with_counter_threads() {
+        extern crate std;
+
+        let mut values = [0u64; 4];
+        let atomic = AtomicPtrWithCounter::<u64>::default();
+        std::thread::scope(|s| {
+            for value in values.iter_mut() {
+                let atomic = &atomic;
+                s.spawn(move || {
+                    let ptr = value as *mut u64;
+                    unsafe { *ptr = 1 };
+                    for _ in 0..1000 {
+                        let _ = atomic.fetch_update(AcqRel, Acquire, |(_, n)| Some((ptr, n + 1)));
+                    }
+                });
+            }
+        });
+        let (ptr, counter) = atomic.load(Acquire);
+        assert_eq!(counter, 4000);
+
+        // Whichever thread won, its pointer came through with its provenance intact.
+        unsafe { *ptr += 10 };
+        assert_eq!(values.iter().sum::<u64>(), 4 + 10);
+        assert_eq!(values.iter().filter(|&&v| v == 11).count(), 1);
+    }
+}
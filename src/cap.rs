@@ -0,0 +1,568 @@
+//! Software emulation of CHERI-style capabilities -- [Cap][].
+//!
+//! On [CHERI][] hardware every pointer is a *capability*: along with the address, it carries
+//! the bounds of the memory it may access, a set of permissions, and a validity tag. Code that
+//! plays fast and loose with provenance (say, by round-tripping a pointer through a `usize`)
+//! produces an untagged capability, and using it traps. That's great for catching bugs, but
+//! few of us have CHERI hardware lying around.
+//!
+//! [`Cap`] is a fat pointer that emulates this in software: it's a real pointer, plus the
+//! bounds and permissions of the allocation it was derived from, plus a tag. [`with_addr`],
+//! [`map_addr`], and friends preserve all of that (just like [`Strict`][crate::Strict]
+//! does), while pointers that come from nowhere ([`Cap::invalid`]) are untagged.
+//! Every access is checked, and violations panic deterministically, so you can port the
+//! pointer-twiddling parts of your code to `Cap` and run your test suite on an ordinary
+//! machine to see if they'd survive on CHERI.
+//!
+//! Unlike real CHERI, moving the address out of bounds doesn't clear the tag; it's only
+//! checked when you actually access memory (which matches Rust's `wrapping_offset` rules).
+//!
+//! [CHERI]: https://www.cl.cam.ac.uk/research/security/ctsrd/cheri/
+//! [`with_addr`]: Cap::with_addr
+//! [`map_addr`]: Cap::map_addr
+
+#[allow(unused_imports)]
+use crate::Strict;
+
+/// A pointer with bounds, permissions, and a validity tag, like a CHERI capability.
+///
+/// See the [module docs][crate::cap] for details.
+pub struct Cap<T> {
+    ptr: *mut T,
+    base: usize,
+    len: usize,
+    perms: Perms,
+    tagged: bool,
+}
+
+/// The permissions of a [`Cap`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Perms(u8);
+
+impl Perms {
+    /// No permissions.
+    pub const NONE: Perms = Perms(0);
+    /// Permission to read through the capability.
+    pub const LOAD: Perms = Perms(1 << 0);
+    /// Permission to write through the capability.
+    pub const STORE: Perms = Perms(1 << 1);
+    /// All permissions.
+    pub const ALL: Perms = Perms(Self::LOAD.0 | Self::STORE.0);
+
+    /// Returns `true` if `self` has all of the permissions in `other`.
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Perms) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The permissions in both `self` and `other`.
+    #[inline]
+    #[must_use]
+    pub const fn intersection(self, other: Perms) -> Perms {
+        Perms(self.0 & other.0)
+    }
+}
+
+/// The reasons an access through a [`Cap`] can fail.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CapError {
+    /// The capability isn't tagged (it was made from an integer).
+    Untagged {
+        /// The address of the capability.
+        addr: usize,
+    },
+    /// The access isn't entirely inside the capability's bounds.
+    OutOfBounds {
+        /// The address of the capability.
+        addr: usize,
+        /// The size of the access.
+        size: usize,
+        /// The lowest address the capability can access.
+        base: usize,
+        /// The number of bytes the capability can access.
+        len: usize,
+    },
+    /// The capability doesn't have permission for the access.
+    PermissionDenied {
+        /// The address of the capability.
+        addr: usize,
+        /// The permissions that were needed.
+        needed: Perms,
+        /// The permissions the capability has.
+        perms: Perms,
+    },
+}
+
+impl core::fmt::Display for CapError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match *self {
+            CapError::Untagged { addr } => {
+                write!(f, "access through untagged capability at {:#x}", addr)
+            }
+            CapError::OutOfBounds {
+                addr,
+                size,
+                base,
+                len,
+            } => write!(
+                f,
+                "{}-byte access at {:#x} is out of the capability's bounds {:#x}..{:#x}",
+                size,
+                addr,
+                base,
+                base.wrapping_add(len)
+            ),
+            CapError::PermissionDenied {
+                addr,
+                needed,
+                perms,
+            } => write!(
+                f,
+                "access at {:#x} needs {:?}, but the capability only has {:?}",
+                addr, needed, perms
+            ),
+        }
+    }
+}
+
+impl<T> Cap<T> {
+    /// Creates a capability for the whole slice, with all permissions.
+    #[inline]
+    #[must_use]
+    pub fn from_slice_mut(slice: &mut [T]) -> Self {
+        let len = core::mem::size_of_val(slice);
+        // SAFETY: the slice's pointer has provenance over exactly its bytes.
+        unsafe { Self::from_raw_parts(slice.as_mut_ptr(), len, Perms::ALL) }
+    }
+
+    /// Creates a capability for the whole slice, with only [`Perms::LOAD`].
+    #[inline]
+    #[must_use]
+    pub fn from_slice(slice: &[T]) -> Self {
+        let len = core::mem::size_of_val(slice);
+        // SAFETY: the slice's pointer has provenance over exactly its bytes.
+        unsafe { Self::from_raw_parts(slice.as_ptr() as *mut T, len, Perms::LOAD) }
+    }
+
+    /// Creates a capability for a single value, with all permissions.
+    #[inline]
+    #[must_use]
+    pub fn from_mut(val: &mut T) -> Self {
+        Self::from_slice_mut(core::slice::from_mut(val))
+    }
+
+    /// Creates a capability for a single value, with only [`Perms::LOAD`].
+    #[inline]
+    #[must_use]
+    pub fn from_ref(val: &T) -> Self {
+        Self::from_slice(core::slice::from_ref(val))
+    }
+
+    /// Creates a capability for the `len` bytes starting at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have provenance over `len` bytes, and must permit the
+    /// accesses in `perms`, for as long as the capability is used.
+    #[inline]
+    #[must_use]
+    pub unsafe fn from_raw_parts(ptr: *mut T, len: usize, perms: Perms) -> Self {
+        Cap {
+            base: ptr.addr(),
+            ptr,
+            len,
+            perms,
+            tagged: true,
+        }
+    }
+
+    /// Creates an untagged capability with the given address.
+    ///
+    /// This is the equivalent of [`invalid_mut`][crate::invalid_mut]:
+    /// any access through it will fail.
+    #[inline]
+    #[must_use]
+    pub fn invalid(addr: usize) -> Self {
+        Cap {
+            ptr: crate::invalid_mut(addr),
+            base: 0,
+            len: 0,
+            perms: Perms::NONE,
+            tagged: false,
+        }
+    }
+
+    /// Gets the address of the capability.
+    #[inline]
+    #[must_use]
+    pub fn addr(&self) -> usize {
+        self.ptr.addr()
+    }
+
+    /// Creates a new capability with the given address, and the same bounds,
+    /// permissions and tag as `self`.
+    #[inline]
+    #[must_use]
+    pub fn with_addr(&self, addr: usize) -> Self {
+        Cap {
+            ptr: self.ptr.with_addr(addr),
+            ..*self
+        }
+    }
+
+    /// Creates a new capability by mapping `self`'s address to a new one.
+    #[inline]
+    #[must_use]
+    pub fn map_addr(&self, f: impl FnOnce(usize) -> usize) -> Self {
+        self.with_addr(f(self.addr()))
+    }
+
+    /// Offsets the capability by `count` elements, wrapping around.
+    #[inline]
+    #[must_use]
+    pub fn wrapping_add(&self, count: usize) -> Self {
+        Cap {
+            ptr: self.ptr.wrapping_add(count),
+            ..*self
+        }
+    }
+
+    /// Offsets the capability by `-count` elements, wrapping around.
+    #[inline]
+    #[must_use]
+    pub fn wrapping_sub(&self, count: usize) -> Self {
+        Cap {
+            ptr: self.ptr.wrapping_sub(count),
+            ..*self
+        }
+    }
+
+    /// Casts the capability to point to a different type, keeping its bounds.
+    #[inline]
+    #[must_use]
+    pub fn cast<U>(&self) -> Cap<U> {
+        Cap {
+            ptr: self.ptr as *mut U,
+            base: self.base,
+            len: self.len,
+            perms: self.perms,
+            tagged: self.tagged,
+        }
+    }
+
+    /// Narrows the bounds of the capability to the `len` bytes starting at its
+    /// current address, like CHERI's `CSetBounds`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new bounds aren't inside the old ones: capabilities can only shrink.
+    #[must_use]
+    #[track_caller]
+    pub fn restrict_bounds(&self, len: usize) -> Self {
+        if let Err(err) = self.check(len, Perms::NONE) {
+            panic!("can't restrict bounds: {}", err);
+        }
+        Cap {
+            base: self.addr(),
+            len,
+            ..*self
+        }
+    }
+
+    /// Removes permissions from the capability, like CHERI's `CAndPerm`.
+    #[inline]
+    #[must_use]
+    pub fn restrict_perms(&self, perms: Perms) -> Self {
+        Cap {
+            perms: self.perms.intersection(perms),
+            ..*self
+        }
+    }
+
+    /// Returns `true` if the capability is tagged (derived from a real pointer).
+    #[inline]
+    #[must_use]
+    pub fn is_tagged(&self) -> bool {
+        self.tagged
+    }
+
+    /// The lowest address the capability can access.
+    #[inline]
+    #[must_use]
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// The number of bytes the capability can access.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the capability can't access any bytes.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The permissions of the capability.
+    #[inline]
+    #[must_use]
+    pub fn perms(&self) -> Perms {
+        self.perms
+    }
+
+    /// Gets the underlying pointer *without* any checks.
+    #[inline]
+    #[must_use]
+    pub fn as_ptr(&self) -> *mut T {
+        self.ptr
+    }
+
+    /// Checks that a `size`-byte access with the given permissions is allowed.
+    pub fn check(&self, size: usize, needed: Perms) -> Result<(), CapError> {
+        let addr = self.addr();
+        if !self.tagged {
+            return Err(CapError::Untagged { addr });
+        }
+        let offset = addr.wrapping_sub(self.base);
+        if offset > self.len || size > self.len - offset {
+            return Err(CapError::OutOfBounds {
+                addr,
+                size,
+                base: self.base,
+                len: self.len,
+            });
+        }
+        if !self.perms.contains(needed) {
+            return Err(CapError::PermissionDenied {
+                addr,
+                needed,
+                perms: self.perms,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that a read of a `T` is allowed, and returns the pointer to do it with.
+    #[inline]
+    pub fn check_load(&self) -> Result<*const T, CapError> {
+        self.check(core::mem::size_of::<T>(), Perms::LOAD)?;
+        Ok(self.ptr)
+    }
+
+    /// Checks that a write of a `T` is allowed, and returns the pointer to do it with.
+    #[inline]
+    pub fn check_store(&self) -> Result<*mut T, CapError> {
+        self.check(core::mem::size_of::<T>(), Perms::STORE)?;
+        Ok(self.ptr)
+    }
+
+    /// Reads the value the capability points to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capability doesn't allow the read.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`ptr::read`][core::ptr::read], except for the bounds and provenance
+    /// requirements that are checked by the capability.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn read(&self) -> T {
+        self.unwrap(self.check_load()).read()
+    }
+
+    /// Writes a value to where the capability points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capability doesn't allow the write.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`ptr::write`][core::ptr::write], except for the bounds and provenance
+    /// requirements that are checked by the capability.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn write(&self, val: T) {
+        self.unwrap(self.check_store()).write(val)
+    }
+
+    /// Gets a reference to the value the capability points to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capability doesn't allow reads.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`pointer::as_ref`], except for the bounds and provenance
+    /// requirements that are checked by the capability.
+    ///
+    /// [`pointer::as_ref`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.as_ref
+    #[inline]
+    #[track_caller]
+    pub unsafe fn as_ref<'a>(&self) -> &'a T {
+        &*self.unwrap(self.check_load())
+    }
+
+    /// Gets a mutable reference to the value the capability points to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capability doesn't allow both reads and writes.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`pointer::as_mut`], except for the bounds and provenance
+    /// requirements that are checked by the capability.
+    ///
+    /// [`pointer::as_mut`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.as_mut
+    #[inline]
+    #[track_caller]
+    pub unsafe fn as_mut<'a>(&self) -> &'a mut T {
+        self.unwrap(self.check_load());
+        &mut *self.unwrap(self.check_store())
+    }
+
+    #[track_caller]
+    fn unwrap<P>(&self, result: Result<P, CapError>) -> P {
+        match result {
+            Ok(ptr) => ptr,
+            Err(err) => panic!("capability violation: {}", err),
+        }
+    }
+}
+
+// These are manually implemented to avoid the bounds on `T` that derive would add.
+impl<T> Copy for Cap<T> {}
+impl<T> Clone for Cap<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+/// Two capabilities are equal if they have the same address, bounds, permissions and tag.
+impl<T> PartialEq for Cap<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+            && self.base == other.base
+            && self.len == other.len
+            && self.perms == other.perms
+            && self.tagged == other.tagged
+    }
+}
+impl<T> Eq for Cap<T> {}
+
+impl<T> core::fmt::Debug for Cap<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.tagged {
+            write!(
+                f,
+                "Cap({:#x} [{:#x}..{:#x}] {:?})",
+                self.addr(),
+                self.base,
+                self.base.wrapping_add(self.len),
+                self.perms
+            )
+        } else {
+            write!(f, "Cap({:#x} untagged)", self.addr())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cap, CapError, Perms};
+
+    #[test]
+    fn test_cap_in_bounds() {
+        let mut arr = [1u32, 2, 3, 4];
+        let cap = Cap::from_slice_mut(&mut arr);
+        assert!(cap.is_tagged());
+        assert_eq!(cap.len(), 16);
+
+        unsafe {
+            let third = cap.wrapping_add(2);
+            assert_eq!(third.read(), 3);
+            third.write(30);
+            *cap.map_addr(|a| a + 12).as_mut() += 1;
+            assert_eq!(*cap.with_addr(cap.base() + 4).as_ref(), 2);
+        }
+        assert_eq!(arr, [1, 2, 30, 5]);
+    }
+
+    #[test]
+    fn test_cap_violations() {
+        let mut arr = [1u32, 2, 3, 4];
+        let cap = Cap::from_slice_mut(&mut arr);
+        let base = cap.base();
+
+        // Going out of bounds is fine, accessing isn't.
+        let past_end = cap.wrapping_add(4);
+        assert_eq!(
+            past_end.check_load(),
+            Err(CapError::OutOfBounds {
+                addr: base + 16,
+                size: 4,
+                base,
+                len: 16
+            })
+        );
+        assert!(past_end.wrapping_sub(1).check_load().is_ok());
+
+        // Straddling the end is out of bounds too.
+        let straddle = cap.map_addr(|a| a + 14);
+        assert!(straddle.check_load().is_err());
+        assert!(straddle.cast::<u8>().check_load().is_ok());
+
+        // Integers make untagged capabilities.
+        let forged = Cap::<u32>::invalid(cap.addr());
+        assert_eq!(
+            forged.check_load(),
+            Err(CapError::Untagged { addr: cap.addr() })
+        );
+
+        // Permissions can only be taken away.
+        let read_only = cap.restrict_perms(Perms::LOAD);
+        assert!(read_only.check_load().is_ok());
+        assert!(read_only.check_store().is_err());
+        assert_eq!(read_only.restrict_perms(Perms::ALL).perms(), Perms::LOAD);
+        assert_eq!(Cap::from_ref(&arr[0]).perms(), Perms::LOAD);
+    }
+
+    #[test]
+    fn test_cap_restrict_bounds() {
+        let mut arr = [1u32, 2, 3, 4];
+        let cap = Cap::from_slice_mut(&mut arr);
+        let middle = cap.wrapping_add(1).restrict_bounds(8);
+        assert_eq!(middle.base(), cap.base() + 4);
+        assert!(middle.check_load().is_ok());
+        assert!(middle.wrapping_add(1).check_load().is_ok());
+        assert!(middle.wrapping_add(2).check_load().is_err());
+        assert!(middle.wrapping_sub(1).check_load().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "capability violation")]
+    fn test_cap_read_panics() {
+        let arr = [1u32, 2, 3, 4];
+        let cap = Cap::from_slice(&arr);
+        let _ = unsafe { cap.wrapping_add(4).read() };
+    }
+
+    #[test]
+    #[should_panic(expected = "can't restrict bounds")]
+    fn test_cap_widen_panics() {
+        let mut x = 5u32;
+        let cap = Cap::from_mut(&mut x).cast::<u64>();
+        let _ = cap.restrict_bounds(8);
+    }
+}
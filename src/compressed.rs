@@ -0,0 +1,319 @@
+//! Base-relative compressed pointers -- [CompressedPtr][] and [Region][].
+
+use crate::region::RawRegion;
+use core::marker::PhantomData;
+
+/// A region of memory that [`CompressedPtr`]s are relative to, like a heap arena.
+///
+/// The whole point of this type is to hold onto the provenance of the region's base
+/// pointer: every pointer decompressed with [`Region::decompress`] is derived from it with
+/// [`with_addr`][crate::Strict::with_addr], so it can access anything the base pointer can.
+///
+/// The lifetime `'a` ties the `Region` to the borrow of the underlying memory when it's
+/// created from a slice, so that decompressed pointers can't outlive it by accident (they
+/// can still be *used* after it's gone, because they're raw pointers).
+#[derive(Copy, Clone, Debug)]
+pub struct Region<'a> {
+    raw: RawRegion<'a>,
+}
+
+/// A pointer stored as an offset from the base of a [`Region`].
+///
+/// This is `Offset` bytes big (4 bytes by default) instead of pointer-sized, at the cost of
+/// needing the `Region` to get a real pointer back. A `CompressedPtr` doesn't remember
+/// which `Region` it came from, so decompressing it with the wrong one will produce garbage.
+///
+/// The maximum offset (e.g. `u32::MAX`) is reserved to represent null.
+#[repr(transparent)]
+pub struct CompressedPtr<T, Offset: self::Offset = u32> {
+    offset: Offset,
+    _marker: PhantomData<*mut T>,
+}
+
+/// The types that can be used as the offset of a [`CompressedPtr`].
+///
+/// This trait is sealed and implemented for `u8`, `u16`, `u32`, `u64`, and `usize`.
+pub trait Offset: Copy + Eq + Ord + core::hash::Hash + core::fmt::Debug + private::Sealed {
+    /// The offset that represents null.
+    const NULL: Self;
+
+    /// Converts the offset into a `usize`, for pointer arithmetic.
+    fn to_usize(self) -> usize;
+
+    /// Converts a `usize` into this offset type, failing if it doesn't fit or is `NULL`.
+    fn from_usize(offset: usize) -> Option<Self>;
+}
+
+macro_rules! offset_impls {
+    ($($int_ty: ident),*) => {
+        $(
+            impl private::Sealed for $int_ty {}
+            impl Offset for $int_ty {
+                // Inherent MAX requires 1.43
+                const NULL: Self = !0;
+
+                #[inline]
+                fn to_usize(self) -> usize {
+                    self as usize
+                }
+
+                #[inline]
+                fn from_usize(offset: usize) -> Option<Self> {
+                    use core::convert::TryFrom;
+                    <$int_ty>::try_from(offset).ok().filter(|&o| o != Self::NULL)
+                }
+            }
+        )*
+    };
+}
+
+offset_impls!(u8, u16, u32, u64, usize);
+
+impl<'a> Region<'a> {
+    /// Creates a `Region` covering the given slice.
+    #[inline]
+    #[must_use]
+    pub fn from_slice_mut<T>(slice: &'a mut [T]) -> Self {
+        Region {
+            raw: RawRegion::from_slice_mut(slice),
+        }
+    }
+
+    /// Creates a `Region` covering the given slice.
+    ///
+    /// Pointers decompressed from this `Region` must not be written through.
+    #[inline]
+    #[must_use]
+    pub fn from_slice<T>(slice: &'a [T]) -> Self {
+        Region {
+            raw: RawRegion::from_slice(slice),
+        }
+    }
+
+    /// Creates a `Region` of `len` bytes starting at `base`.
+    ///
+    /// This is safe, like `Provenance::from_raw_parts`: the `Region` only makes raw
+    /// pointers, and it's on you to only dereference the ones that `base`'s provenance
+    /// actually allows.
+    #[inline]
+    #[must_use]
+    pub fn from_raw_parts(base: *mut u8, len: usize) -> Self {
+        Region {
+            raw: RawRegion::from_raw_parts(base, len),
+        }
+    }
+
+    /// Gets the base pointer of the region.
+    #[inline]
+    #[must_use]
+    pub fn base(&self) -> *mut u8 {
+        self.raw.base()
+    }
+
+    /// Gets the length of the region in bytes.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Returns `true` if the region is zero bytes long.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.raw.len() == 0
+    }
+
+    /// Returns `true` if the whole `T` that `ptr` points to is inside the region.
+    #[inline]
+    #[must_use]
+    pub fn contains<T>(&self, ptr: *const T) -> bool {
+        self.raw.offset_of(ptr).is_some()
+    }
+
+    /// Compresses `ptr` to an offset from the base of this region.
+    ///
+    /// A null `ptr` compresses to [`CompressedPtr::null`]. Returns `None` if the `T` that
+    /// `ptr` points to isn't entirely inside the region, or if the offset doesn't fit.
+    #[inline]
+    #[must_use]
+    pub fn compress<T, O: Offset>(&self, ptr: *const T) -> Option<CompressedPtr<T, O>> {
+        if ptr.is_null() {
+            return Some(CompressedPtr::null());
+        }
+        let offset = O::from_usize(self.raw.offset_of(ptr)?)?;
+        Some(CompressedPtr {
+            offset,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Decompresses `ptr` back into a real pointer, with the provenance of this region.
+    ///
+    /// A null `ptr` decompresses to a null pointer.
+    ///
+    /// In debug builds this asserts that the result is inside the region.
+    #[inline]
+    #[must_use]
+    pub fn decompress<T, O: Offset>(&self, ptr: CompressedPtr<T, O>) -> *mut T {
+        if ptr.is_null() {
+            return self.raw.null();
+        }
+        let offset = ptr.offset.to_usize();
+        debug_assert!(
+            self.raw.fits::<T>(offset),
+            "CompressedPtr offset {} is out of bounds of a Region of length {}",
+            offset,
+            self.raw.len()
+        );
+        self.raw.at(offset)
+    }
+}
+
+impl<T, O: Offset> CompressedPtr<T, O> {
+    /// Creates a null `CompressedPtr`.
+    #[inline]
+    #[must_use]
+    pub fn null() -> Self {
+        CompressedPtr {
+            offset: O::NULL,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this `CompressedPtr` is null.
+    #[inline]
+    #[must_use]
+    pub fn is_null(self) -> bool {
+        self.offset == O::NULL
+    }
+
+    /// Gets the raw offset from the base of the region.
+    #[inline]
+    #[must_use]
+    pub fn offset(self) -> O {
+        self.offset
+    }
+}
+
+// These are manually implemented to avoid the bounds on `T` that derive would add.
+impl<T, O: Offset> Copy for CompressedPtr<T, O> {}
+impl<T, O: Offset> Clone for CompressedPtr<T, O> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T, O: Offset> PartialEq for CompressedPtr<T, O> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+impl<T, O: Offset> Eq for CompressedPtr<T, O> {}
+impl<T, O: Offset> PartialOrd for CompressedPtr<T, O> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T, O: Offset> Ord for CompressedPtr<T, O> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.offset.cmp(&other.offset)
+    }
+}
+impl<T, O: Offset> core::hash::Hash for CompressedPtr<T, O> {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.offset.hash(state)
+    }
+}
+impl<T, O: Offset> Default for CompressedPtr<T, O> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+impl<T, O: Offset> core::fmt::Debug for CompressedPtr<T, O> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.is_null() {
+            write!(f, "CompressedPtr(null)")
+        } else {
+            write!(f, "CompressedPtr({:?})", self.offset)
+        }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompressedPtr, Region};
+
+    #[test]
+    fn test_compressed_roundtrip() {
+        let mut arena = [0u64; 16];
+        let elem_3 = &arena[3] as *const u64;
+        let outside = 0u64;
+
+        let region = Region::from_slice_mut(&mut arena);
+        assert_eq!(region.len(), 16 * 8);
+
+        let compressed: CompressedPtr<u64> = region.compress(elem_3).unwrap();
+        assert_eq!(compressed.offset(), 3 * 8);
+
+        let ptr = region.decompress(compressed);
+        assert_eq!(ptr as *const u64, elem_3);
+        unsafe {
+            *ptr = 7;
+            *region.decompress(compressed) += 1;
+            assert_eq!(*(region.base() as *mut u64).add(3), 8);
+        }
+
+        // Entirely or partially outside the region.
+        assert!(region.compress::<u64, u32>(&outside).is_none());
+        let tail = unsafe { region.base().add(15 * 8 + 4) };
+        assert!(region.compress::<u64, u32>(tail as *const u64).is_none());
+        assert!(region.compress::<u8, u32>(tail).is_some());
+    }
+
+    #[test]
+    fn test_compressed_offset_overflow() {
+        let mut bytes = [0u8; 300];
+        let region = Region::from_slice_mut(&mut bytes);
+        let at = |offset| unsafe { region.base().add(offset) };
+
+        assert_eq!(region.compress::<u8, u8>(at(254)).unwrap().offset(), 254);
+        // 255 is reserved for null, and 256 doesn't fit at all.
+        assert!(region.compress::<u8, u8>(at(255)).is_none());
+        assert!(region.compress::<u8, u8>(at(256)).is_none());
+        assert_eq!(region.compress::<u8, u16>(at(256)).unwrap().offset(), 256);
+    }
+
+    #[test]
+    fn test_compressed_null() {
+        let arena = [0u32; 4];
+        let region = Region::from_slice(&arena);
+
+        let null = region.compress::<u32, u16>(core::ptr::null()).unwrap();
+        assert!(null.is_null());
+        assert_eq!(null, CompressedPtr::default());
+        assert!(region.decompress(null).is_null());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is out of bounds of a Region of length 16")]
+    fn test_compressed_bounds() {
+        let arena = [0u32; 4];
+        let other_arena = [0u32; 64];
+        let big_region = Region::from_slice(&other_arena);
+        let small_region = Region::from_slice(&arena);
+
+        let far = big_region.compress::<u32, u32>(&other_arena[32]).unwrap();
+        let _ = small_region.decompress(far);
+    }
+}
@@ -0,0 +1,253 @@
+//! Debug checking for [`expose_addr`][crate::Strict::expose_addr] and
+//! [`from_exposed_addr`][crate::from_exposed_addr].
+//!
+//! With the `expose_tracking` feature enabled, every call to `expose_addr` records the
+//! range of bytes the pointer points to in a global [`ExposeRegistry`], and every call to
+//! `from_exposed_addr`/`from_exposed_addr_mut` panics if the address it's given was never
+//! exposed. This is a (much weaker) stand-in for what Miri checks, for when you can't run
+//! your code under Miri (FFI, inline assembly, the test suite takes a week...).
+//!
+//! Only the bytes of the pointee are recorded, not the whole allocation it's part of, because
+//! we have no way of knowing what that allocation is. So this check is deliberately more
+//! conservative than the real rules: if you expose a pointer to the first element of an
+//! array and then reconstruct a pointer to the second, it will panic. To expose the whole
+//! array, expose a pointer to the whole array.
+//!
+//! The address `0` is always allowed, so that `from_exposed_addr(0)` can produce null.
+//!
+//! With the `std` feature, a default registry is used unless you install your own with
+//! [`set_registry`]. Without it, you *must* install one before exposing anything.
+//!
+//! Requires 1.46, and 1.63 with `std` (for the `const` `Mutex::new` in the default registry).
+//!
+//! **This is a debugging aid: it never forgets an exposed address, and it takes a lock on
+//! every operation.**
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A global set of exposed address ranges.
+///
+/// Implement this and call [`set_registry`] to use exposure tracking without `std`.
+pub trait ExposeRegistry: Sync {
+    /// Records that the `len` bytes starting at `addr` have been exposed.
+    fn expose(&self, addr: usize, len: usize);
+
+    /// Returns `true` if `addr` is in (or one-past-the-end of) a range that was exposed.
+    fn is_exposed(&self, addr: usize) -> bool;
+}
+
+/// The error returned by [`set_registry`] if a registry has already been installed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SetRegistryError;
+
+impl core::fmt::Display for SetRegistryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "an expose registry has already been installed")
+    }
+}
+
+const UNINITIALIZED: usize = 0;
+const INITIALIZING: usize = 1;
+const INITIALIZED: usize = 2;
+
+/// A write-once slot for the installed registry.
+///
+/// `registry` is only written by the thread that moves `state` from `UNINITIALIZED` to
+/// `INITIALIZING`, and only read after observing `INITIALIZED`.
+struct RegistryCell {
+    state: AtomicUsize,
+    registry: UnsafeCell<Option<&'static dyn ExposeRegistry>>,
+}
+
+// SAFETY: see the comment on RegistryCell.
+unsafe impl Sync for RegistryCell {}
+
+static REGISTRY: RegistryCell = RegistryCell {
+    state: AtomicUsize::new(UNINITIALIZED),
+    registry: UnsafeCell::new(None),
+};
+
+/// Installs the global [`ExposeRegistry`].
+///
+/// This can only be done once, and should be done before any pointers are exposed,
+/// since earlier exposures are recorded in the default registry (or nowhere, without `std`).
+///
+/// ```
+/// use sptr::expose::ExposeRegistry;
+/// use std::sync::Mutex;
+///
+/// struct Ranges(Mutex<Vec<(usize, usize)>>);
+///
+/// impl ExposeRegistry for Ranges {
+///     fn expose(&self, addr: usize, len: usize) {
+///         self.0.lock().unwrap().push((addr, addr + len));
+///     }
+///     fn is_exposed(&self, addr: usize) -> bool {
+///         self.0.lock().unwrap().iter().any(|&(start, end)| start <= addr && addr <= end)
+///     }
+/// }
+///
+/// static RANGES: Ranges = Ranges(Mutex::new(Vec::new()));
+/// sptr::expose::set_registry(&RANGES).unwrap();
+///
+/// #[allow(unstable_name_collisions, deprecated)]
+/// {
+///     use sptr::Strict;
+///     let x = 5u32;
+///     let addr = (&x as *const u32).expose_addr();
+///     assert!(RANGES.is_exposed(addr));
+///     assert_eq!(unsafe { *sptr::from_exposed_addr::<u32>(addr) }, 5);
+/// }
+/// ```
+pub fn set_registry(registry: &'static dyn ExposeRegistry) -> Result<(), SetRegistryError> {
+    match REGISTRY.state.compare_exchange(
+        UNINITIALIZED,
+        INITIALIZING,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => {
+            // SAFETY: we're the only thread that can get here, and nobody reads
+            // the registry until they observe INITIALIZED.
+            unsafe {
+                *REGISTRY.registry.get() = Some(registry);
+            }
+            REGISTRY.state.store(INITIALIZED, Ordering::Release);
+            Ok(())
+        }
+        Err(_) => Err(SetRegistryError),
+    }
+}
+
+/// Gets the global [`ExposeRegistry`], if there is one.
+#[must_use]
+pub fn registry() -> Option<&'static dyn ExposeRegistry> {
+    if REGISTRY.state.load(Ordering::Acquire) == INITIALIZED {
+        // SAFETY: the registry is never written again once the state is INITIALIZED.
+        return unsafe { *REGISTRY.registry.get() };
+    }
+    default_registry()
+}
+
+#[cfg(feature = "std")]
+fn default_registry() -> Option<&'static dyn ExposeRegistry> {
+    static DEFAULT: StdRegistry = StdRegistry {
+        ranges: std::sync::Mutex::new(std::collections::BTreeMap::new()),
+    };
+    Some(&DEFAULT)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_registry() -> Option<&'static dyn ExposeRegistry> {
+    None
+}
+
+fn expect_registry() -> &'static dyn ExposeRegistry {
+    registry().expect(
+        "sptr's expose_tracking feature is enabled, but no registry was installed \
+         with sptr::expose::set_registry (or enable the std feature for a default one)",
+    )
+}
+
+/// Called by `expose_addr`.
+#[inline]
+pub(crate) fn record(addr: usize, len: usize) {
+    expect_registry().expose(addr, len);
+}
+
+/// Called by `from_exposed_addr`.
+#[inline]
+#[track_caller]
+pub(crate) fn check(addr: usize) {
+    if addr != 0 && !expect_registry().is_exposed(addr) {
+        panic!(
+            "from_exposed_addr({:#x}) was called, but no pointer to that address \
+             was ever exposed with expose_addr",
+            addr
+        );
+    }
+}
+
+/// The default registry: a sorted map of disjoint `start -> end` ranges.
+#[cfg(feature = "std")]
+struct StdRegistry {
+    ranges: std::sync::Mutex<std::collections::BTreeMap<usize, usize>>,
+}
+
+#[cfg(feature = "std")]
+impl ExposeRegistry for StdRegistry {
+    fn expose(&self, addr: usize, len: usize) {
+        let mut ranges = self.ranges.lock().unwrap_or_else(|e| e.into_inner());
+        let mut start = addr;
+        let mut end = addr.saturating_add(len);
+
+        // Merge with every range we overlap or touch, so that lookups only
+        // need to check the closest range below them.
+        if let Some((&prev_start, &prev_end)) = ranges.range(..=start).next_back() {
+            if prev_end >= start {
+                start = prev_start;
+                end = end.max(prev_end);
+            }
+        }
+        let merged: std::vec::Vec<usize> = ranges.range(start..=end).map(|(&s, _)| s).collect();
+        for merged_start in merged {
+            if let Some(merged_end) = ranges.remove(&merged_start) {
+                end = end.max(merged_end);
+            }
+        }
+        ranges.insert(start, end);
+    }
+
+    fn is_exposed(&self, addr: usize) -> bool {
+        let ranges = self.ranges.lock().unwrap_or_else(|e| e.into_inner());
+        match ranges.range(..=addr).next_back() {
+            Some((_, &end)) => addr <= end,
+            None => false,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::{ExposeRegistry, StdRegistry};
+    use crate::Strict;
+
+    #[test]
+    fn test_std_registry() {
+        let registry = StdRegistry {
+            ranges: std::sync::Mutex::new(std::collections::BTreeMap::new()),
+        };
+        registry.expose(100, 10);
+        registry.expose(200, 10);
+        assert!(registry.is_exposed(100));
+        assert!(registry.is_exposed(110));
+        assert!(!registry.is_exposed(111));
+        assert!(!registry.is_exposed(99));
+
+        // Bridge the gap, and everything should get merged.
+        registry.expose(105, 100);
+        assert!(registry.is_exposed(150));
+        assert!(registry.is_exposed(210));
+        assert_eq!(registry.ranges.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_expose_tracking() {
+        let arr = [1u32, 2, 3, 4];
+        let addr = (&arr as *const [u32; 4]).expose_addr();
+        let third = crate::from_exposed_addr::<u32>(addr + 8);
+        assert_eq!(unsafe { *third }, 3);
+        assert!(crate::from_exposed_addr::<u32>(0).is_null());
+    }
+
+    #[test]
+    #[should_panic(expected = "was ever exposed")]
+    #[allow(deprecated)]
+    fn test_unexposed_panics() {
+        static NEVER_EXPOSED: u64 = 0;
+        let addr = (&NEVER_EXPOSED as *const u64).addr();
+        let _ = crate::from_exposed_addr_mut::<u64>(addr);
+    }
+}
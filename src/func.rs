@@ -70,14 +70,142 @@ impl OpaqueFnPtr {
 
     /// Get the address of the function pointer.
     ///
-    /// Note that while you *can* compare this to a data pointer, the result will
-    /// almost certainly be meaningless, especially on platforms like WASM and AVR
-    /// where function pointers are in a separate address-space from data pointers.
+    /// The result is a [`CodeAddr`], so it can't be compared to the address of a data
+    /// pointer, which would almost certainly be meaningless, especially on platforms like WASM
+    /// and AVR where function pointers are in a separate address-space from data pointers.
     ///
     /// See [`pointer::addr`][crate::Strict::addr] for details.
     #[inline]
     #[must_use]
-    pub fn addr(self) -> usize {
-        self.0 as usize
+    pub fn addr(self) -> CodeAddr {
+        CodeAddr(self.0 as usize)
+    }
+}
+
+/// Compares the *addresses* of the two function pointers for equality.
+///
+/// This is the same as `f == g`, but is spelled out to make it clear that it's an
+/// inherently unreliable operation: the same function can have several different addresses
+/// (e.g. one per codegen unit), and different functions can be merged to share one address.
+/// So a `true` result means "these were the same address", and nothing more.
+///
+/// If the signatures of the two functions differ, convert them with
+/// [`OpaqueFnPtr::from_fn`] first.
+#[inline]
+#[must_use]
+pub fn fn_addr_eq(f: OpaqueFnPtr, g: OpaqueFnPtr) -> bool {
+    f.addr() == g.addr()
+}
+
+/// The address of a function pointer, in the *code* address-space.
+///
+/// Harvard Architectures (WASM, AVR) put functions in a separate address-space from data,
+/// so the "same" integer can refer to completely unrelated things depending on which kind of
+/// pointer it came from. This type exists so that mixing the two up is a type error: it
+/// supports comparisons, offsetting, and formatting, but it deliberately can't be converted
+/// to a `usize`, and so can't be fed into [`with_addr`][crate::Strict::with_addr] or any
+/// other API that expects the address of a data pointer.
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeAddr(usize);
+
+impl CodeAddr {
+    /// Offsets this address by `rhs` bytes, wrapping around at the boundary of the type.
+    #[inline]
+    #[must_use]
+    pub const fn wrapping_add(self, rhs: usize) -> Self {
+        CodeAddr(self.0.wrapping_add(rhs))
+    }
+
+    /// Offsets this address by `-rhs` bytes, wrapping around at the boundary of the type.
+    #[inline]
+    #[must_use]
+    pub const fn wrapping_sub(self, rhs: usize) -> Self {
+        CodeAddr(self.0.wrapping_sub(rhs))
+    }
+
+    /// Offsets this address by `rhs` bytes, returning `None` if that overflows.
+    #[inline]
+    #[must_use]
+    pub fn checked_add(self, rhs: usize) -> Option<Self> {
+        self.0.checked_add(rhs).map(CodeAddr)
+    }
+
+    /// Offsets this address by `-rhs` bytes, returning `None` if that overflows.
+    #[inline]
+    #[must_use]
+    pub fn checked_sub(self, rhs: usize) -> Option<Self> {
+        self.0.checked_sub(rhs).map(CodeAddr)
+    }
+}
+
+impl core::ops::Add<usize> for CodeAddr {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: usize) -> Self::Output {
+        CodeAddr(self.0 + rhs)
+    }
+}
+impl core::ops::Sub<usize> for CodeAddr {
+    type Output = Self;
+    #[inline]
+    fn sub(self, rhs: usize) -> Self::Output {
+        CodeAddr(self.0 - rhs)
+    }
+}
+/// The distance in bytes between two code addresses.
+impl core::ops::Sub<CodeAddr> for CodeAddr {
+    type Output = usize;
+    #[inline]
+    fn sub(self, rhs: CodeAddr) -> Self::Output {
+        self.0 - rhs.0
+    }
+}
+impl core::ops::AddAssign<usize> for CodeAddr {
+    #[inline]
+    fn add_assign(&mut self, rhs: usize) {
+        self.0 += rhs;
+    }
+}
+impl core::ops::SubAssign<usize> for CodeAddr {
+    #[inline]
+    fn sub_assign(&mut self, rhs: usize) {
+        self.0 -= rhs;
+    }
+}
+// Masks are useful for things like the Thumb bit on ARM.
+impl core::ops::BitAnd<usize> for CodeAddr {
+    type Output = Self;
+    #[inline]
+    fn bitand(self, rhs: usize) -> Self::Output {
+        CodeAddr(self.0 & rhs)
+    }
+}
+impl core::ops::BitOr<usize> for CodeAddr {
+    type Output = Self;
+    #[inline]
+    fn bitor(self, rhs: usize) -> Self::Output {
+        CodeAddr(self.0 | rhs)
+    }
+}
+
+impl core::fmt::Debug for CodeAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "CodeAddr({:#x})", self.0)
+    }
+}
+impl core::fmt::Display for CodeAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+impl core::fmt::LowerHex for CodeAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+impl core::fmt::UpperHex for CodeAddr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::UpperHex::fmt(&self.0, f)
     }
 }
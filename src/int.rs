@@ -1,6 +1,7 @@
 //! Pointers Pretending To Be Integers For Crimes -- [uptr][] and [iptr][].
 
 #![allow(unstable_name_collisions)]
+#[allow(unused_imports)]
 use crate::Strict;
 
 /// A pointer that pretends to be an integer, for API Crimes.
@@ -46,12 +47,18 @@ macro_rules! int_impls {
             // Inherent MIN/MAX requires 1.43
             // pub const MIN: $self_ty = Self::from_int(<$int_ty>::MIN);
             // pub const MAX: $self_ty = Self::from_int(<$int_ty>::MAX);
+            #[allow(clippy::legacy_numeric_constants)]
             pub const MIN: $self_ty = Self::from_int(core::$int_ty::MIN);
+            #[allow(clippy::legacy_numeric_constants)]
             pub const MAX: $self_ty = Self::from_int(core::$int_ty::MAX);
 
-            // Inherent BITS requires 1.53
-            // pub const BITS: u32 = <$int_ty>::BITS;
-            pub const BITS: u32 = core::mem::size_of::<$int_ty>() as u32 * 8;
+            /// The number of bits of the *integer* this type pretends to be, which is
+            /// [`ADDR_BITS`][crate::ADDR_BITS], not the size of the pointer it's stored in.
+            pub const BITS: u32 = crate::ADDR_BITS;
+
+            /// The number of bits of the pointer this type is actually stored in
+            /// ([`PTR_BITS`][crate::PTR_BITS]), which may be more than [`Self::BITS`].
+            pub const PTR_BITS: u32 = crate::PTR_BITS;
 
             #[inline]
             #[must_use]
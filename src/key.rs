@@ -0,0 +1,213 @@
+//! Using pointers as map keys by address -- [PtrKey][].
+//!
+//! ```
+//! use sptr::PtrKey;
+//! use std::collections::HashMap;
+//!
+//! let names = ["alice", "bob"];
+//! let mut visits = HashMap::new();
+//! *visits.entry(PtrKey::from(&names[1])).or_insert(0) += 1;
+//! *visits.entry(PtrKey::from(&names[1])).or_insert(0) += 1;
+//! assert_eq!(visits[&PtrKey::from(&names[1])], 2);
+//! assert!(!visits.contains_key(&PtrKey::from(&names[0])));
+//! ```
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use core::ptr::NonNull;
+
+/// A pointer that compares, orders and hashes by its address only.
+///
+/// Raw pointers already implement `Hash`, `Eq` and `Ord`, but for wide pointers those include
+/// the metadata (so two `*const dyn Trait` to the same object can be unequal if they came
+/// from different vtables), and getting the address out for a custom hasher usually means
+/// `as usize`. `PtrKey` only ever looks at [`addr`][crate::Strict::addr], so it never exposes
+/// the pointer's provenance, and it keeps the original pointer so you can get it back.
+///
+/// Since only the address is compared, two keys for different objects at the same address
+/// (like a struct and its first field, or two zero-sized values) are equal.
+pub struct PtrKey<T: ?Sized> {
+    ptr: *const T,
+}
+
+impl<T: ?Sized> PtrKey<T> {
+    /// Creates a key for `ptr`.
+    #[inline]
+    #[must_use]
+    pub fn new(ptr: *const T) -> Self {
+        PtrKey { ptr }
+    }
+
+    /// Gets the address of the pointer, which is all that the key compares.
+    #[inline]
+    #[must_use]
+    pub fn addr(&self) -> usize {
+        (self.ptr as *const u8).addr()
+    }
+
+    /// Gets back the original pointer.
+    #[inline]
+    #[must_use]
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    /// Gets back the original pointer, as mutable.
+    ///
+    /// This is only allowed to be written through if the key was created from a pointer
+    /// that was.
+    #[inline]
+    #[must_use]
+    pub fn as_mut_ptr(&self) -> *mut T {
+        self.ptr as *mut T
+    }
+
+    /// Gets back the original pointer as a `NonNull`, if it isn't null.
+    #[inline]
+    #[must_use]
+    pub fn as_non_null(&self) -> Option<NonNull<T>> {
+        NonNull::new(self.ptr as *mut T)
+    }
+}
+
+impl<T: ?Sized> From<*const T> for PtrKey<T> {
+    #[inline]
+    fn from(ptr: *const T) -> Self {
+        PtrKey::new(ptr)
+    }
+}
+
+impl<T: ?Sized> From<*mut T> for PtrKey<T> {
+    #[inline]
+    fn from(ptr: *mut T) -> Self {
+        PtrKey::new(ptr)
+    }
+}
+
+impl<T: ?Sized> From<NonNull<T>> for PtrKey<T> {
+    #[inline]
+    fn from(ptr: NonNull<T>) -> Self {
+        PtrKey::new(ptr.as_ptr())
+    }
+}
+
+impl<'a, T: ?Sized> From<&'a T> for PtrKey<T> {
+    #[inline]
+    fn from(r: &'a T) -> Self {
+        PtrKey::new(r)
+    }
+}
+
+impl<'a, T: ?Sized> From<&'a mut T> for PtrKey<T> {
+    #[inline]
+    fn from(r: &'a mut T) -> Self {
+        PtrKey::new(r)
+    }
+}
+
+// Manual impls to avoid requiring `T: Clone` and friends.
+impl<T: ?Sized> Copy for PtrKey<T> {}
+impl<T: ?Sized> Clone for PtrKey<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> PartialEq for PtrKey<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.addr() == other.addr()
+    }
+}
+impl<T: ?Sized> Eq for PtrKey<T> {}
+
+impl<T: ?Sized> PartialOrd for PtrKey<T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T: ?Sized> Ord for PtrKey<T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.addr().cmp(&other.addr())
+    }
+}
+
+impl<T: ?Sized> Hash for PtrKey<T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.addr());
+    }
+}
+
+impl<T: ?Sized> core::fmt::Debug for PtrKey<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("PtrKey")
+            .field(&(self.ptr as *const u8))
+            .finish()
+    }
+}
+
+impl<T: ?Sized> core::fmt::Pointer for PtrKey<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Pointer::fmt(&(self.ptr as *const u8), f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PtrKey;
+    use core::hash::{Hash, Hasher};
+    use core::ptr::NonNull;
+
+    /// Records what's written to it, to check that only the address is hashed.
+    #[derive(Default)]
+    struct Recorder(usize, usize);
+
+    impl Hasher for Recorder {
+        fn finish(&self) -> u64 {
+            self.0 as u64
+        }
+        fn write(&mut self, _: &[u8]) {
+            self.1 += 100;
+        }
+        fn write_usize(&mut self, i: usize) {
+            self.0 = i;
+            self.1 += 1;
+        }
+    }
+
+    #[test]
+    fn test_key() {
+        let arr = [1u32, 2, 3];
+        let a = PtrKey::from(&arr[0]);
+        let b = PtrKey::from(NonNull::from(&arr[2]));
+        assert!(a < b);
+        assert_eq!(a, PtrKey::new(arr.as_ptr()));
+        assert_eq!(b.addr() - a.addr(), 8);
+        assert_eq!(b.as_non_null(), Some(NonNull::from(&arr[2])));
+        assert!(PtrKey::new(core::ptr::null::<u8>()).as_non_null().is_none());
+
+        let mut hasher = Recorder::default();
+        b.hash(&mut hasher);
+        assert_eq!((hasher.0, hasher.1), (b.addr(), 1));
+    }
+
+    #[test]
+    fn test_wide_key() {
+        let arr = [1u32, 2, 3];
+        // Same address, different lengths.
+        let whole = PtrKey::<[u32]>::from(&arr[..]);
+        let prefix = PtrKey::<[u32]>::from(&arr[..1]);
+        assert_eq!(whole, prefix);
+        assert_eq!(unsafe { (&*whole.as_ptr()).len() }, 3);
+
+        let mut hasher = Recorder::default();
+        whole.hash(&mut hasher);
+        assert_eq!(hasher.1, 1);
+    }
+}
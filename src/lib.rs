@@ -13,6 +13,18 @@
 //! * `pub fn `[`invalid_mut`]`<T>(addr: usize) -> *mut T;`
 //! * `pub fn `[`from_exposed_addr`]`<T>(addr: usize) -> *const T;`
 //! * `pub fn `[`from_exposed_addr_mut`]`<T>(addr: usize) -> *mut T;`
+//! * `pub fn `[`addr_eq`]`<T: ?Sized, U: ?Sized>(p: *const T, q: *const U) -> bool;`
+//! * `pub fn `[`from_ref`]`<T: ?Sized>(r: &T) -> *const T;`
+//! * `pub fn `[`from_mut`]`<T: ?Sized>(r: &mut T) -> *mut T;`
+//! * `pub fn `[`as_ptr_range`]`<T>(slice: &[T]) -> Range<*const T>;` (a method on slices in std)
+//! * `pub fn `[`as_mut_ptr_range`]`<T>(slice: &mut [T]) -> Range<*mut T>;` (a method on slices in std)
+//! * `pub fn `[`fn_addr_eq`]`(f: OpaqueFnPtr, g: OpaqueFnPtr) -> bool;` (feature = opaque_fn)
+//! * `pub fn `[`metadata`]`<T: ?Sized + Pointee>(ptr: *const T) -> <T as Pointee>::Metadata;` (feature = ptr_meta, requires 1.42)
+//! * `pub fn `[`from_raw_parts`]`<T: ?Sized + Pointee>(data: *const (), meta: <T as Pointee>::Metadata) -> *const T;` (feature = ptr_meta, requires 1.42)
+//! * `pub fn `[`from_raw_parts_mut`]`<T: ?Sized + Pointee>(data: *mut (), meta: <T as Pointee>::Metadata) -> *mut T;` (feature = ptr_meta, requires 1.42)
+//! * `pub fn `[`to_raw_parts`]`<T: ?Sized + Pointee>(ptr: *const T) -> (*const (), <T as Pointee>::Metadata);` (a method in std, feature = ptr_meta, requires 1.42)
+//! * `pub fn `[`to_raw_parts_mut`]`<T: ?Sized + Pointee>(ptr: *mut T) -> (*mut (), <T as Pointee>::Metadata);` (a method in std, feature = ptr_meta, requires 1.42)
+//! * `pub unsafe trait `[`Pointee`]`;` and `pub struct `[`DynMetadata`]`<Dyn: ?Sized>;` (trait objects need [`dyn_pointee!`]; feature = ptr_meta, requires 1.42)
 //!
 //!
 //! ## core::pointer (sptr::Strict)
@@ -21,13 +33,66 @@
 //! * `pub fn `[`expose_addr`]`(self) -> usize;`
 //! * `pub fn `[`with_addr`]`(self, addr: usize) -> Self;`
 //! * `pub fn `[`map_addr`]`(self, f: impl FnOnce(usize) -> usize) -> Self;`
+//! * `pub unsafe fn `[`offset_from_unsigned`][Strict::offset_from_unsigned]`(self, origin: *const T) -> usize;`
+//! * `pub unsafe fn `[`byte_offset_from_unsigned`][Strict::byte_offset_from_unsigned]`<U: ?Sized>(self, origin: *const U) -> usize;`
+//! * `pub fn `[`addr_distance`][Strict::addr_distance]`<U: ?Sized>(self, origin: *const U) -> usize;` (non-standard)
+//!
+//!
+//! ## core::ptr::NonNull (sptr::StrictNonNull)
+//!
+//! * `pub unsafe fn `[`offset_from_unsigned`][StrictNonNull::offset_from_unsigned]`(self, subtracted: NonNull<T>) -> usize;`
+//! * `pub unsafe fn `[`byte_offset_from_unsigned`][StrictNonNull::byte_offset_from_unsigned]`<U: ?Sized>(self, origin: NonNull<U>) -> usize;`
+//! * `pub fn `[`addr_distance`][StrictNonNull::addr_distance]`<U: ?Sized>(self, origin: NonNull<U>) -> usize;` (non-standard)
+//! * `pub fn `[`from_ref`][StrictNonNull::from_ref]`(r: &T) -> NonNull<T>;`
+//! * `pub fn `[`from_mut`][StrictNonNull::from_mut]`(r: &mut T) -> NonNull<T>;`
+//!
+//!
+//! ## core::pointer for slices (sptr::StrictSlice)
+//!
+//! (feature = slice_ptr, requires 1.46)
+//!
+//! * `pub fn `[`len`][StrictSlice::len]`(self) -> usize;`
+//! * `pub fn `[`is_empty`][StrictSlice::is_empty]`(self) -> bool;`
+//! * `pub fn `[`as_ptr`][StrictSlice::as_ptr]`(self) -> *const T;` (or `*mut T`)
+//! * `pub fn `[`with_addr`][StrictSlice::with_addr]`(self, addr: usize) -> Self;`
+//! * `pub unsafe fn `[`get_unchecked`][StrictSlice::get_unchecked]`(self, index: usize) -> *const T;` (or `*mut T`)
+//! * `pub fn `[`split_at`][StrictSlice::split_at]`(self, mid: usize) -> (Self, Self);` (non-standard)
+//!
+//!
+//! ## core::ptr::NonNull for slices (sptr::StrictNonNullSlice)
+//!
+//! (feature = slice_ptr, requires 1.46)
+//!
+//! * `pub fn `[`len`][StrictNonNullSlice::len]`(self) -> usize;`
+//! * `pub fn `[`is_empty`][StrictNonNullSlice::is_empty]`(self) -> bool;`
+//! * `pub fn `[`as_non_null_ptr`][StrictNonNullSlice::as_non_null_ptr]`(self) -> NonNull<T>;`
+//! * `pub fn `[`as_mut_ptr`][StrictNonNullSlice::as_mut_ptr]`(self) -> *mut T;`
+//! * `pub fn `[`with_addr`][StrictNonNullSlice::with_addr]`(self, addr: NonZeroUsize) -> Self;`
+//! * `pub unsafe fn `[`get_unchecked_mut`][StrictNonNullSlice::get_unchecked_mut]`(self, index: usize) -> NonNull<T>;`
+//! * `pub fn `[`split_at`][StrictNonNullSlice::split_at]`(self, mid: usize) -> (Self, Self);` (non-standard)
 //!
 //!
 //! ## NON-STANDARD EXTENSIONS (disabled by default, use at your own risk)
 //!
+//! * `sptr::`[`Addr`], `sptr::`[`ADDR_BITS`], `sptr::`[`PTR_BITS`] (always available)
+//! * `sptr::`[`const_addr`], `sptr::`[`const_with_addr`], `sptr::`[`const_wrapping_byte_add`] and their `_mut` versions (always available, `const` with feature = const_fn)
 //! * `sptr::`[`uptr`] (feature = uptr)
 //! * `sptr::`[`iptr`] (feature = uptr)
 //! * `sptr::`[`OpaqueFnPtr`] (feature = opaque_fn)
+//! * `sptr::`[`CodeAddr`] (feature = opaque_fn)
+//! * `sptr::`[`RelPtr`] (feature = rel_ptr, requires 1.42)
+//! * `sptr::`[`CompressedPtr`] and `sptr::`[`Region`] (feature = compressed)
+//! * `sptr::`[`swizzle`] (feature = swizzle)
+//! * `sptr::`[`expose`] (feature = expose_tracking, optionally with std; requires 1.46, 1.63 with std)
+//! * `sptr::`[`Cap`] (feature = cap)
+//! * `sptr::`[`Provenance`] and `sptr::`[`ProvenanceArena`] (feature = provenance)
+//! * `sptr::`[`PtrRange`] (feature = ptr_range)
+//! * `sptr::`[`PtrKey`] (feature = ptr_key)
+//! * `sptr::`[`PtrOrInt`] (feature = ptr_or_int)
+//! * `sptr::`[`NanBox`] (feature = nan_box, 64-bit targets only)
+//! * `sptr::`[`XorPtr`] and `sptr::`[`MangledPtr`] (feature = xor_ptr)
+//! * `sptr::`[`AtomicTaggedPtr`] (feature = atomic)
+//! * `sptr::`[`AtomicPtrWithCounter`] (feature = atomic)
 //!
 //!
 //!
@@ -48,9 +113,34 @@
 //! println!("{}", ptr.addr());
 //! ```
 //!
-//! By default, this crate will also mark methods on pointers as "deprecated" if they are
-//! incompatible with strict_provenance. If you don't want this, set `default-features = false`
-//! in your Cargo.toml.
+//! With the `deprecate_non_strict` feature, this crate will also mark the APIs that are
+//! incompatible with strict_provenance as "deprecated": [`expose_addr`][Strict::expose_addr]
+//! and [`from_exposed_addr`] (and `_mut`), which rely on exposed provenance. This is off by
+//! default, since those are the sanctioned escape hatch when you really do need to get a
+//! pointer back from an integer, but turning it on is a quick way to find every place that
+//! does.
+//!
+//! Finding the casts that need to be migrated is its own chore, so the `sptr-audit` tool
+//! in this repository reports every `ptr as usize`, `usize as *const T` and pointer<->integer
+//! `transmute`, along with the API it should use instead. It exits with a non-zero status if
+//! it finds anything, so it can be used to gate CI. It isn't part of sptr's workspace, since
+//! it needs a much newer compiler than sptr itself:
+//!
+//! ```text
+//! $ cargo run --manifest-path sptr-audit/Cargo.toml -- src/
+//! src/lib.rs:12:13: needs addr: p as usize
+//! src/lib.rs:13:13: needs with_addr: (p as usize + 4) as *mut u8
+//! ```
+//!
+//! The `sptr-fix` tool next to it rewrites the mechanical cases for you (`p as usize` to
+//! `p.addr()`, `(p as usize + n) as *mut T` to `p.map_addr(|a| a + n)`, `0x1000 as *const T`
+//! to `sptr::invalid(0x1000)`), adds the `use sptr::Strict;` and `unstable_name_collisions`
+//! allow, and marks everything else with a `// TODO(sptr-fix)` comment. It prints a diff
+//! rather than touching any files:
+//!
+//! ```text
+//! $ cargo run --manifest-path sptr-audit/Cargo.toml --bin sptr-fix -- src/ | git apply
+//! ```
 //!
 //! Rust is the canonical source of definitions for these APIs and semantics, but the docs
 //! here will vaguely try to mirror the docs checked into Rust.
@@ -361,6 +451,43 @@
 //! [Strict Provenance]: https://github.com/rust-lang/rust/issues/95228
 //! [Stacked Borrows]: https://plv.mpi-sws.org/rustbelt/stacked-borrows/
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "slice_ptr")]
+use core::num::NonZeroUsize;
+use core::ptr::NonNull;
+
+/// The type of the "address" portion of a pointer, like C's `ptraddr_t`.
+///
+/// Rust currently defines `usize` to be pointer-sized, and so this is just `usize`.
+/// But on platforms like [CHERI][], a pointer is 128 bits while its address is only 64,
+/// and the address is the only part that can meaningfully be treated as an integer.
+/// This alias exists so that code can say which of the two notions it means, and
+/// all of the [`Strict`] APIs are written in terms of it.
+///
+/// [CHERI]: https://www.cl.cam.ac.uk/research/security/ctsrd/cheri/
+pub type Addr = usize;
+
+/// The number of bits in an [`Addr`].
+///
+/// This is what you want for bit-twiddling addresses, e.g. for tagged pointers.
+pub const ADDR_BITS: u32 = core::mem::size_of::<Addr>() as u32 * 8;
+
+/// The number of bits in a (thin) pointer.
+///
+/// This is the same as [`ADDR_BITS`] on every platform Rust currently supports, but it
+/// won't be on CHERI, so code should not assume they are equal. If you want to check that
+/// your code doesn't, build with `RUSTFLAGS="--cfg sptr_simulate_wide_ptr"`: this constant
+/// will then claim pointers are twice as wide as addresses. This is deliberately not a cargo
+/// feature, since it changes the value of a public constant for every crate in the build.
+pub const PTR_BITS: u32 = core::mem::size_of::<*const ()>() as u32 * 8 * PTR_BITS_SCALE;
+
+#[cfg(not(sptr_simulate_wide_ptr))]
+const PTR_BITS_SCALE: u32 = 1;
+#[cfg(sptr_simulate_wide_ptr)]
+const PTR_BITS_SCALE: u32 = 2;
+
 /// Creates an invalid pointer with the given address.
 ///
 /// This is different from `addr as *const T`, which creates a pointer that picks up a previously
@@ -381,7 +508,7 @@
 /// see the [module documentation][crate] for details.
 #[inline(always)]
 #[must_use]
-pub const fn invalid<T>(addr: usize) -> *const T {
+pub const fn invalid<T>(addr: Addr) -> *const T {
     // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
     // We use transmute rather than a cast so tools like Miri can tell that this
     // is *not* the same as from_exposed_addr.
@@ -414,7 +541,7 @@ pub const fn invalid<T>(addr: usize) -> *const T {
 /// see the [module documentation][crate] for details.
 #[inline(always)]
 #[must_use]
-pub const fn invalid_mut<T>(addr: usize) -> *mut T {
+pub const fn invalid_mut<T>(addr: Addr) -> *mut T {
     // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
     // We use transmute rather than a cast so tools like Miri can tell that this
     // is *not* the same as from_exposed_addr.
@@ -452,12 +579,25 @@ pub const fn invalid_mut<T>(addr: usize) -> *mut T {
 ///
 /// This API and its claimed semantics are part of the Strict Provenance experiment, see the
 /// [module documentation][crate] for details.
+///
+/// With the `deprecate_non_strict` feature, this is deprecated so that uses of it
+/// show up as warnings.
 #[must_use]
 #[inline]
-pub fn from_exposed_addr<T>(addr: usize) -> *const T
+#[cfg_attr(feature = "expose_tracking", track_caller)]
+#[cfg_attr(
+    feature = "deprecate_non_strict",
+    deprecated(
+        note = "incompatible with strict provenance, use `with_addr` on an existing pointer"
+    )
+)]
+pub fn from_exposed_addr<T>(addr: Addr) -> *const T
 where
     T: Sized,
 {
+    #[cfg(feature = "expose_tracking")]
+    expose::check(addr);
+
     // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
     addr as *const T
 }
@@ -487,21 +627,305 @@ where
 ///
 /// This API and its claimed semantics are part of the Strict Provenance experiment, see the
 /// [module documentation][crate] for details.
+///
+/// With the `deprecate_non_strict` feature, this is deprecated so that uses of it
+/// show up as warnings.
 #[must_use]
 #[inline]
-pub fn from_exposed_addr_mut<T>(addr: usize) -> *mut T
+#[cfg_attr(feature = "expose_tracking", track_caller)]
+#[cfg_attr(
+    feature = "deprecate_non_strict",
+    deprecated(
+        note = "incompatible with strict provenance, use `with_addr` on an existing pointer"
+    )
+)]
+pub fn from_exposed_addr_mut<T>(addr: Addr) -> *mut T
 where
     T: Sized,
 {
+    #[cfg(feature = "expose_tracking")]
+    expose::check(addr);
+
     // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
     addr as *mut T
 }
 
+/// Compares the *addresses* of the two pointers for equality,
+/// ignoring any metadata in fat pointers.
+///
+/// If the arguments are thin pointers of the same type,
+/// then this is the same as [`eq`][core::ptr::eq].
+///
+/// This is mostly useful for `*const dyn Trait`, where the same object can be
+/// reached through several different vtables (e.g. one per codegen unit), so comparing
+/// the whole fat pointer can spuriously return `false`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fmt::{Debug, Display};
+///
+/// let x = 5u32;
+/// let as_debug: *const dyn Debug = &x;
+/// let as_display: *const dyn Display = &x;
+/// assert!(sptr::addr_eq(as_debug, as_display));
+///
+/// let whole: *const [u32] = &[1, 2, 3][..];
+/// let first: *const u32 = whole as *const u32;
+/// assert!(sptr::addr_eq(whole, first));
+/// ```
+#[must_use]
+#[inline]
+pub fn addr_eq<T: ?Sized, U: ?Sized>(p: *const T, q: *const U) -> bool {
+    (p as *const ()) == (q as *const ())
+}
+
+/// Defines a function that's a `const fn` with the `const_fn` feature, and a normal `fn`
+/// without it, since the things these do are only allowed in const eval since 1.61.
+macro_rules! maybe_const_fn {
+    ($(#[$attr:meta])* pub fn $($rest:tt)*) => {
+        #[cfg(feature = "const_fn")]
+        $(#[$attr])*
+        pub const fn $($rest)*
+
+        #[cfg(not(feature = "const_fn"))]
+        $(#[$attr])*
+        pub fn $($rest)*
+    };
+}
+
+maybe_const_fn! {
+/// A `const fn` version of [`addr`][Strict::addr].
+///
+/// Const eval doesn't know where allocations will end up in memory, so during const eval
+/// only pointers that were made from an integer (like [`invalid`] ones) have an address,
+/// and using this on any other pointer is a compile error. At runtime it's the same as
+/// [`addr`][Strict::addr].
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+///
+#[cfg_attr(feature = "const_fn", doc = "```")]
+#[cfg_attr(not(feature = "const_fn"), doc = "```ignore")]
+/// const ADDR: usize = sptr::const_addr(sptr::invalid::<u8>(0x1000));
+/// assert_eq!(ADDR, 0x1000);
+/// ```
+#[inline(always)]
+#[must_use]
+// The lint is about exactly the limitation documented above.
+#[allow(unknown_lints, ptr_to_integer_transmute_in_consts)]
+#[allow(clippy::transmutes_expressible_as_ptr_casts, clippy::not_unsafe_ptr_arg_deref)]
+pub fn const_addr<T>(ptr: *const T) -> Addr {
+    // SAFETY: Pointer-to-integer transmutes are valid (if you are okay with losing the
+    // provenance), and unlike casts they're allowed in a `const fn`.
+    unsafe { core::mem::transmute::<*const T, Addr>(ptr) }
+}
+}
+
+maybe_const_fn! {
+/// A `const fn` version of [`with_addr`][Strict::with_addr].
+///
+/// This needs the address of `ptr`, so during const eval it has the same limitations as
+/// [`const_addr`]. If you know how far away the new address is instead (like when tagging
+/// the low bits of an aligned pointer, where `|` is the same as `+`), use
+/// [`const_wrapping_byte_add`], which works on any pointer.
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+#[inline(always)]
+#[must_use]
+pub fn const_with_addr<T>(ptr: *const T, addr: Addr) -> *const T {
+    const_wrapping_byte_add(ptr, addr.wrapping_sub(const_addr(ptr)))
+}
+}
+
+maybe_const_fn! {
+/// A `const fn` version of [`with_addr`][Strict::with_addr] for mutable pointers.
+///
+/// See [`const_with_addr`] for the limitations.
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+#[inline(always)]
+#[must_use]
+pub fn const_with_addr_mut<T>(ptr: *mut T, addr: Addr) -> *mut T {
+    const_with_addr(ptr as *const T, addr) as *mut T
+}
+}
+
+maybe_const_fn! {
+/// A `const fn` version of `ptr.cast::<u8>().wrapping_add(count).cast::<T>()`.
+///
+/// This only moves the pointer relative to where it already is, so it works during const
+/// eval on any pointer, including pointers to `static`s whose address isn't known yet.
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+///
+#[cfg_attr(feature = "const_fn", doc = "```")]
+#[cfg_attr(not(feature = "const_fn"), doc = "```ignore")]
+/// #![allow(unstable_name_collisions)]
+/// use sptr::Strict;
+///
+/// struct Entry(*const u64);
+/// unsafe impl Sync for Entry {}
+///
+/// static A: u64 = 1;
+/// static B: u64 = 2;
+/// // Tag each entry in its low bits, which are 0 since `u64` is 8-aligned.
+/// static TABLE: [Entry; 2] = [
+///     Entry(sptr::const_wrapping_byte_add(&A, 1)),
+///     Entry(sptr::const_wrapping_byte_add(&B, 2)),
+/// ];
+///
+/// let tagged = TABLE[1].0;
+/// assert_eq!(tagged.addr() & 7, 2);
+/// assert_eq!(unsafe { *tagged.map_addr(|a| a & !7) }, 2);
+/// ```
+#[inline(always)]
+#[must_use]
+pub fn const_wrapping_byte_add<T>(ptr: *const T, count: usize) -> *const T {
+    (ptr as *const u8).wrapping_add(count) as *const T
+}
+}
+
+maybe_const_fn! {
+/// A `const fn` version of `ptr.cast::<u8>().wrapping_add(count).cast::<T>()` for mutable
+/// pointers.
+///
+/// See [`const_wrapping_byte_add`].
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+#[inline(always)]
+#[must_use]
+pub fn const_wrapping_byte_add_mut<T>(ptr: *mut T, count: usize) -> *mut T {
+    (ptr as *mut u8).wrapping_add(count) as *mut T
+}
+}
+
+maybe_const_fn! {
+/// Converts a reference to a raw pointer.
+///
+/// This is the same as `r as *const T`, but it can't accidentally change the type (or
+/// mutability) too, which makes it easier to spot the casts that actually matter.
+///
+/// This is only a `const fn` with the `const_fn` feature, which requires 1.61.
+///
+/// ```
+/// let x = 5u32;
+/// let ptr = sptr::from_ref(&x);
+/// assert_eq!(unsafe { *ptr }, 5);
+/// ```
+#[inline(always)]
+#[must_use]
+pub fn from_ref<T: ?Sized>(r: &T) -> *const T {
+    r
+}
+}
+
+/// Converts a mutable reference to a raw pointer.
+///
+/// This is the same as `r as *mut T`, but it can't accidentally change the type too.
+///
+/// Unlike std's version, this isn't a `const fn`, since that needs 1.83.
+#[inline(always)]
+#[must_use]
+pub fn from_mut<T: ?Sized>(r: &mut T) -> *mut T {
+    r
+}
+
+/// Gets pointers to the start and (one past the) end of a slice.
+///
+/// Both pointers have the provenance of the whole slice: the end is computed from the start,
+/// so you can walk from either one to the other. This is the same as `<[T]>::as_ptr_range`,
+/// which requires 1.48, and the result can be turned into a [`PtrRange`][] (with the
+/// `ptr_range` feature).
+///
+/// [`PtrRange`]: https://docs.rs/sptr/latest/sptr/range/struct.PtrRange.html
+///
+/// ```
+/// let arr = [1u32, 2, 3];
+/// let range = sptr::as_ptr_range(&arr);
+/// assert_eq!(unsafe { *range.end.sub(1) }, 3);
+/// ```
+#[inline]
+#[must_use]
+pub fn as_ptr_range<T>(slice: &[T]) -> core::ops::Range<*const T> {
+    let start = slice.as_ptr();
+    start..start.wrapping_add(slice.len())
+}
+
+/// Gets mutable pointers to the start and (one past the) end of a slice.
+///
+/// See [`as_ptr_range`]. This is the same as `<[T]>::as_mut_ptr_range`, which requires 1.48.
+#[inline]
+#[must_use]
+pub fn as_mut_ptr_range<T>(slice: &mut [T]) -> core::ops::Range<*mut T> {
+    let start = slice.as_mut_ptr();
+    start..start.wrapping_add(slice.len())
+}
+
 mod private {
     pub trait Sealed {}
+
+    /// Splitting pointers to `Sized` types, slices and `str` into a data pointer and
+    /// metadata, and putting them back together. This is the part of [`Pointee`] that
+    /// [`RelPointee`] needs, so both are built on it.
+    ///
+    /// [`Pointee`]: crate::meta::Pointee
+    /// [`RelPointee`]: crate::rel::RelPointee
+    #[cfg(any(feature = "rel_ptr", feature = "ptr_meta"))]
+    pub trait RawParts {
+        type Metadata: Copy + Default + Send + Sync + Ord + core::hash::Hash + core::fmt::Debug;
+
+        fn metadata(ptr: *const Self) -> Self::Metadata;
+
+        /// The result has the provenance and address of `data`.
+        fn from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self;
+    }
+
+    #[cfg(any(feature = "rel_ptr", feature = "ptr_meta"))]
+    impl<T> RawParts for T {
+        type Metadata = ();
+
+        #[inline]
+        fn metadata(_ptr: *const Self) -> Self::Metadata {}
+
+        #[inline]
+        fn from_raw_parts(data: *const (), _meta: Self::Metadata) -> *const Self {
+            data as *const T
+        }
+    }
+
+    #[cfg(any(feature = "rel_ptr", feature = "ptr_meta"))]
+    impl<T> RawParts for [T] {
+        type Metadata = usize;
+
+        #[inline]
+        fn metadata(ptr: *const Self) -> Self::Metadata {
+            crate::slice_len(ptr)
+        }
+
+        #[inline]
+        fn from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+            core::ptr::slice_from_raw_parts(data as *const T, meta)
+        }
+    }
+
+    #[cfg(any(feature = "rel_ptr", feature = "ptr_meta"))]
+    impl RawParts for str {
+        type Metadata = usize;
+
+        #[inline]
+        fn metadata(ptr: *const Self) -> Self::Metadata {
+            crate::slice_len(ptr as *const [u8])
+        }
+
+        #[inline]
+        fn from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+            core::ptr::slice_from_raw_parts(data as *const u8, meta) as *const str
+        }
+    }
 }
 
 pub trait Strict: private::Sealed {
+    /// The type this pointer points to.
     type Pointee;
     /// Gets the "address" portion of the pointer.
     ///
@@ -527,7 +951,7 @@ pub trait Strict: private::Sealed {
     /// might change in the future (including possibly weakening this so it becomes wholly
     /// equivalent to `self as usize`). See the [module documentation][crate] for details.
     #[must_use]
-    fn addr(self) -> usize
+    fn addr(self) -> Addr
     where
         Self::Pointee: Sized;
 
@@ -554,9 +978,16 @@ pub trait Strict: private::Sealed {
     /// This API and its claimed semantics are part of the Strict Provenance experiment, see the
     /// [module documentation][crate] for details.
     ///
+    /// With the `deprecate_non_strict` feature, this is deprecated so that uses of it show
+    /// up as warnings.
+    ///
     /// [`from_exposed_addr`]: crate::from_exposed_addr
     #[must_use]
-    fn expose_addr(self) -> usize
+    #[cfg_attr(
+        feature = "deprecate_non_strict",
+        deprecated(note = "incompatible with strict provenance, use `addr` and `with_addr`")
+    )]
+    fn expose_addr(self) -> Addr
     where
         Self::Pointee: Sized;
 
@@ -575,7 +1006,7 @@ pub trait Strict: private::Sealed {
     ///
     /// [`wrapping_offset`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.wrapping_offset
     #[must_use]
-    fn with_addr(self, addr: usize) -> Self
+    fn with_addr(self, addr: Addr) -> Self
     where
         Self::Pointee: Sized;
 
@@ -586,7 +1017,55 @@ pub trait Strict: private::Sealed {
     /// This API and its claimed semantics are part of the Strict Provenance experiment,
     /// see the [module documentation][crate] for details.
     #[must_use]
-    fn map_addr(self, f: impl FnOnce(usize) -> usize) -> Self
+    fn map_addr(self, f: impl FnOnce(Addr) -> Addr) -> Self
+    where
+        Self::Pointee: Sized;
+
+    /// Calculates the distance between two pointers, in units of `T`, where it's known that
+    /// `self` is equal to or greater than `origin`.
+    ///
+    /// This is equivalent to `self.offset_from(origin) as usize`, but without the sign
+    /// juggling, and mirrors `pointer::offset_from_unsigned` (formerly `sub_ptr`).
+    ///
+    /// # Safety
+    ///
+    /// All the requirements of [`offset_from`][] apply, and additionally `self` must not be
+    /// less than `origin`. In debug builds, this is asserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is a zero-sized type.
+    ///
+    /// [`offset_from`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.offset_from
+    #[must_use]
+    unsafe fn offset_from_unsigned(self, origin: *const Self::Pointee) -> usize
+    where
+        Self::Pointee: Sized;
+
+    /// Calculates the distance between two pointers, in bytes, where it's known that
+    /// `self` is equal to or greater than `origin`.
+    ///
+    /// This is [`offset_from_unsigned`][Strict::offset_from_unsigned] for `u8` pointers,
+    /// and mirrors `pointer::byte_offset_from_unsigned`. Any metadata of `origin` is ignored.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`offset_from_unsigned`][Strict::offset_from_unsigned].
+    #[must_use]
+    unsafe fn byte_offset_from_unsigned<U: ?Sized>(self, origin: *const U) -> usize
+    where
+        Self::Pointee: Sized;
+
+    /// Calculates the distance in bytes from `origin`'s address to `self`'s, wrapping around
+    /// if `self` is less than `origin`.
+    ///
+    /// This is just `self.addr().wrapping_sub(origin.addr())`, so unlike the methods above it
+    /// is safe and has no requirements: the pointers don't even need to be in the same
+    /// allocation. Any metadata of `origin` is ignored.
+    ///
+    /// This is a non-standard extension.
+    #[must_use]
+    fn addr_distance<U: ?Sized>(self, origin: *const U) -> usize
     where
         Self::Pointee: Sized;
 }
@@ -599,7 +1078,7 @@ impl<T> Strict for *mut T {
 
     #[must_use]
     #[inline]
-    fn addr(self) -> usize
+    fn addr(self) -> Addr
     where
         T: Sized,
     {
@@ -611,17 +1090,22 @@ impl<T> Strict for *mut T {
 
     #[must_use]
     #[inline]
-    fn expose_addr(self) -> usize
+    fn expose_addr(self) -> Addr
     where
         T: Sized,
     {
         // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
-        self as usize
+        let addr = self as usize;
+
+        #[cfg(feature = "expose_tracking")]
+        expose::record(addr, core::mem::size_of::<T>());
+
+        addr
     }
 
     #[must_use]
     #[inline]
-    fn with_addr(self, addr: usize) -> Self
+    fn with_addr(self, addr: Addr) -> Self
     where
         T: Sized,
     {
@@ -642,12 +1126,36 @@ impl<T> Strict for *mut T {
 
     #[must_use]
     #[inline]
-    fn map_addr(self, f: impl FnOnce(usize) -> usize) -> Self
+    fn map_addr(self, f: impl FnOnce(Addr) -> Addr) -> Self
     where
         T: Sized,
     {
         self.with_addr(f(self.addr()))
     }
+
+    #[inline]
+    unsafe fn offset_from_unsigned(self, origin: *const T) -> usize
+    where
+        T: Sized,
+    {
+        Strict::offset_from_unsigned(self as *const T, origin)
+    }
+
+    #[inline]
+    unsafe fn byte_offset_from_unsigned<U: ?Sized>(self, origin: *const U) -> usize
+    where
+        T: Sized,
+    {
+        Strict::byte_offset_from_unsigned(self as *const T, origin)
+    }
+
+    #[inline]
+    fn addr_distance<U: ?Sized>(self, origin: *const U) -> usize
+    where
+        T: Sized,
+    {
+        self.addr().wrapping_sub((origin as *const u8).addr())
+    }
 }
 
 impl<T> Strict for *const T {
@@ -655,7 +1163,7 @@ impl<T> Strict for *const T {
 
     #[must_use]
     #[inline]
-    fn addr(self) -> usize
+    fn addr(self) -> Addr
     where
         T: Sized,
     {
@@ -667,17 +1175,22 @@ impl<T> Strict for *const T {
 
     #[must_use]
     #[inline]
-    fn expose_addr(self) -> usize
+    fn expose_addr(self) -> Addr
     where
         T: Sized,
     {
         // FIXME(strict_provenance_magic): I am magic and should be a compiler intrinsic.
-        self as usize
+        let addr = self as usize;
+
+        #[cfg(feature = "expose_tracking")]
+        expose::record(addr, core::mem::size_of::<T>());
+
+        addr
     }
 
     #[must_use]
     #[inline]
-    fn with_addr(self, addr: usize) -> Self
+    fn with_addr(self, addr: Addr) -> Self
     where
         T: Sized,
     {
@@ -698,12 +1211,426 @@ impl<T> Strict for *const T {
 
     #[must_use]
     #[inline]
-    fn map_addr(self, f: impl FnOnce(usize) -> usize) -> Self
+    fn map_addr(self, f: impl FnOnce(Addr) -> Addr) -> Self
     where
         T: Sized,
     {
         self.with_addr(f(self.addr()))
     }
+
+    #[inline]
+    unsafe fn offset_from_unsigned(self, origin: *const T) -> usize
+    where
+        T: Sized,
+    {
+        let size = core::mem::size_of::<T>();
+        assert!(size != 0, "offset_from_unsigned of a zero-sized type");
+        debug_assert!(
+            self.addr() >= origin.addr(),
+            "offset_from_unsigned called with self ({:#x}) less than origin ({:#x})",
+            self.addr(),
+            origin.addr()
+        );
+        Strict::byte_offset_from_unsigned(self, origin) / size
+    }
+
+    #[inline]
+    unsafe fn byte_offset_from_unsigned<U: ?Sized>(self, origin: *const U) -> usize
+    where
+        T: Sized,
+    {
+        let origin = (origin as *const u8).addr();
+        debug_assert!(
+            self.addr() >= origin,
+            "byte_offset_from_unsigned called with self ({:#x}) less than origin ({:#x})",
+            self.addr(),
+            origin
+        );
+        self.addr() - origin
+    }
+
+    #[inline]
+    fn addr_distance<U: ?Sized>(self, origin: *const U) -> usize
+    where
+        T: Sized,
+    {
+        self.addr().wrapping_sub((origin as *const u8).addr())
+    }
+}
+
+/// The [`Strict`] APIs that also exist on [`NonNull`].
+///
+/// Like `Strict`, this needs to be imported to overlay std's methods.
+pub trait StrictNonNull: private::Sealed {
+    /// The type this `NonNull` points to.
+    type Pointee;
+
+    /// Calculates the distance between two pointers, in units of `T`, where it's known that
+    /// `self` is equal to or greater than `subtracted`.
+    ///
+    /// See [`Strict::offset_from_unsigned`].
+    ///
+    /// # Safety
+    ///
+    /// The same as [`Strict::offset_from_unsigned`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is a zero-sized type.
+    #[must_use]
+    unsafe fn offset_from_unsigned(self, subtracted: NonNull<Self::Pointee>) -> usize
+    where
+        Self::Pointee: Sized;
+
+    /// Calculates the distance between two pointers, in bytes, where it's known that
+    /// `self` is equal to or greater than `origin`.
+    ///
+    /// See [`Strict::byte_offset_from_unsigned`].
+    ///
+    /// # Safety
+    ///
+    /// The same as [`Strict::offset_from_unsigned`].
+    #[must_use]
+    unsafe fn byte_offset_from_unsigned<U: ?Sized>(self, origin: NonNull<U>) -> usize
+    where
+        Self::Pointee: Sized;
+
+    /// Calculates the distance in bytes from `origin`'s address to `self`'s, wrapping around
+    /// if `self` is less than `origin`.
+    ///
+    /// See [`Strict::addr_distance`]. This is a non-standard extension.
+    #[must_use]
+    fn addr_distance<U: ?Sized>(self, origin: NonNull<U>) -> usize
+    where
+        Self::Pointee: Sized;
+
+    /// Converts a reference to a `NonNull`.
+    ///
+    /// This is the same as `NonNull::from(r)`, but can't be confused with other
+    /// conversions. See [`from_ref`].
+    #[must_use]
+    fn from_ref(r: &Self::Pointee) -> Self;
+
+    /// Converts a mutable reference to a `NonNull`.
+    ///
+    /// See [`from_mut`].
+    #[must_use]
+    fn from_mut(r: &mut Self::Pointee) -> Self;
+}
+
+impl<T> private::Sealed for NonNull<T> {}
+
+impl<T> StrictNonNull for NonNull<T> {
+    type Pointee = T;
+
+    #[inline]
+    unsafe fn offset_from_unsigned(self, subtracted: NonNull<T>) -> usize
+    where
+        T: Sized,
+    {
+        Strict::offset_from_unsigned(self.as_ptr() as *const T, subtracted.as_ptr())
+    }
+
+    #[inline]
+    unsafe fn byte_offset_from_unsigned<U: ?Sized>(self, origin: NonNull<U>) -> usize
+    where
+        T: Sized,
+    {
+        Strict::byte_offset_from_unsigned(self.as_ptr() as *const T, origin.as_ptr())
+    }
+
+    #[inline]
+    fn addr_distance<U: ?Sized>(self, origin: NonNull<U>) -> usize
+    where
+        T: Sized,
+    {
+        Strict::addr_distance(self.as_ptr() as *const T, origin.as_ptr())
+    }
+
+    #[inline]
+    fn from_ref(r: &T) -> Self {
+        NonNull::from(r)
+    }
+
+    #[inline]
+    fn from_mut(r: &mut T) -> Self {
+        NonNull::from(r)
+    }
+}
+
+/// Methods for slice pointers (`*const [T]` and `*mut [T]`).
+///
+/// Most of these are in std by now, but some only recently, so this lets you use them on
+/// older compilers too. Custom allocators in particular hand out `*mut [u8]` all the time.
+///
+/// Requires 1.46.
+// The names match std's, and the pointers are `Copy`.
+#[cfg(feature = "slice_ptr")]
+#[allow(clippy::wrong_self_convention)]
+pub trait StrictSlice: private::Sealed {
+    /// The pointer type of the elements: `*const T` or `*mut T`.
+    type ElemPtr;
+
+    /// Gets the number of elements in the slice, without dereferencing it.
+    ///
+    /// This works on any slice pointer, including dangling ones, except that a slice pointer
+    /// whose data pointer is null always has length 0.
+    #[must_use]
+    fn len(self) -> usize;
+
+    /// Returns `true` if the slice has no elements.
+    #[must_use]
+    fn is_empty(self) -> bool;
+
+    /// Gets a pointer to the first element, with the slice's provenance.
+    #[must_use]
+    fn as_ptr(self) -> Self::ElemPtr;
+
+    /// Creates a new slice pointer with the given address and the same length (and
+    /// provenance) as `self`.
+    ///
+    /// See [`Strict::with_addr`].
+    #[must_use]
+    fn with_addr(self, addr: Addr) -> Self;
+
+    /// Gets a pointer to the element at `index`, without bounds checking.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and `self` must point into a single allocation, in the
+    /// sense of [`pointer::add`].
+    ///
+    /// [`pointer::add`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.add
+    #[must_use]
+    unsafe fn get_unchecked(self, index: usize) -> Self::ElemPtr;
+
+    /// Splits the slice pointer in two at `mid`, like [`slice::split_at`].
+    ///
+    /// The halves both keep the slice's provenance. This doesn't dereference anything, so
+    /// it's safe to use on any slice pointer. This is a non-standard extension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    #[must_use]
+    fn split_at(self, mid: usize) -> (Self, Self)
+    where
+        Self: Sized;
+}
+
+/// Methods for `NonNull<[T]>`.
+///
+/// These are the [`StrictSlice`] methods, renamed where std's `NonNull` already has a
+/// method with the same name but a different meaning.
+///
+/// Requires 1.46.
+#[cfg(feature = "slice_ptr")]
+#[allow(clippy::wrong_self_convention)]
+pub trait StrictNonNullSlice: private::Sealed {
+    /// The element type.
+    type Elem;
+
+    /// Gets the number of elements in the slice, without dereferencing it.
+    #[must_use]
+    fn len(self) -> usize;
+
+    /// Returns `true` if the slice has no elements.
+    #[must_use]
+    fn is_empty(self) -> bool;
+
+    /// Gets a pointer to the first element, with the slice's provenance.
+    ///
+    /// This is [`StrictSlice::as_ptr`], but `NonNull<[T]>::as_ptr` already means
+    /// something else.
+    #[must_use]
+    fn as_non_null_ptr(self) -> NonNull<Self::Elem>;
+
+    /// Gets a raw pointer to the first element, with the slice's provenance.
+    #[must_use]
+    fn as_mut_ptr(self) -> *mut Self::Elem;
+
+    /// Creates a new slice pointer with the given address and the same length (and
+    /// provenance) as `self`.
+    #[must_use]
+    fn with_addr(self, addr: NonZeroUsize) -> Self;
+
+    /// Gets a pointer to the element at `index`, without bounds checking.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`StrictSlice::get_unchecked`].
+    #[must_use]
+    unsafe fn get_unchecked_mut(self, index: usize) -> NonNull<Self::Elem>;
+
+    /// Splits the slice pointer in two at `mid`, like [`slice::split_at`].
+    ///
+    /// See [`StrictSlice::split_at`]. This is a non-standard extension.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`, or if the second half would wrap around to null.
+    #[must_use]
+    fn split_at(self, mid: usize) -> (Self, Self)
+    where
+        Self: Sized;
+}
+
+/// Gets the length of a slice pointer without dereferencing it.
+///
+/// There's no way to get at the length of a null slice pointer without `<*const [T]>::len`
+/// (1.79), so those are treated as empty.
+#[cfg(any(feature = "slice_ptr", feature = "rel_ptr", feature = "ptr_meta"))]
+#[inline]
+fn slice_len<T>(ptr: *const [T]) -> usize {
+    if (ptr as *const T).is_null() {
+        0
+    } else {
+        // SAFETY: a slice of ZSTs is valid to reference at any non-null address, since it
+        // doesn't cover any memory and `()` is 1-aligned.
+        unsafe { (&*(ptr as *const [()])).len() }
+    }
+}
+
+#[cfg(feature = "slice_ptr")]
+impl<T> private::Sealed for *mut [T] {}
+#[cfg(feature = "slice_ptr")]
+impl<T> private::Sealed for *const [T] {}
+#[cfg(feature = "slice_ptr")]
+impl<T> private::Sealed for NonNull<[T]> {}
+
+#[cfg(feature = "slice_ptr")]
+impl<T> StrictSlice for *mut [T] {
+    type ElemPtr = *mut T;
+
+    #[inline]
+    fn len(self) -> usize {
+        slice_len(self)
+    }
+
+    #[inline]
+    fn is_empty(self) -> bool {
+        StrictSlice::len(self) == 0
+    }
+
+    #[inline]
+    fn as_ptr(self) -> *mut T {
+        self as *mut T
+    }
+
+    #[inline]
+    fn with_addr(self, addr: Addr) -> Self {
+        let ptr = Strict::with_addr(StrictSlice::as_ptr(self), addr);
+        core::ptr::slice_from_raw_parts_mut(ptr, StrictSlice::len(self))
+    }
+
+    #[inline]
+    unsafe fn get_unchecked(self, index: usize) -> *mut T {
+        StrictSlice::as_ptr(self).add(index)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let len = StrictSlice::len(self);
+        assert!(mid <= len, "mid > len");
+        let ptr = StrictSlice::as_ptr(self);
+        (
+            core::ptr::slice_from_raw_parts_mut(ptr, mid),
+            core::ptr::slice_from_raw_parts_mut(ptr.wrapping_add(mid), len - mid),
+        )
+    }
+}
+
+#[cfg(feature = "slice_ptr")]
+impl<T> StrictSlice for *const [T] {
+    type ElemPtr = *const T;
+
+    #[inline]
+    fn len(self) -> usize {
+        slice_len(self)
+    }
+
+    #[inline]
+    fn is_empty(self) -> bool {
+        StrictSlice::len(self) == 0
+    }
+
+    #[inline]
+    fn as_ptr(self) -> *const T {
+        self as *const T
+    }
+
+    #[inline]
+    fn with_addr(self, addr: Addr) -> Self {
+        let ptr = Strict::with_addr(StrictSlice::as_ptr(self), addr);
+        core::ptr::slice_from_raw_parts(ptr, StrictSlice::len(self))
+    }
+
+    #[inline]
+    unsafe fn get_unchecked(self, index: usize) -> *const T {
+        StrictSlice::as_ptr(self).add(index)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let len = StrictSlice::len(self);
+        assert!(mid <= len, "mid > len");
+        let ptr = StrictSlice::as_ptr(self);
+        (
+            core::ptr::slice_from_raw_parts(ptr, mid),
+            core::ptr::slice_from_raw_parts(ptr.wrapping_add(mid), len - mid),
+        )
+    }
+}
+
+#[cfg(feature = "slice_ptr")]
+impl<T> StrictNonNullSlice for NonNull<[T]> {
+    type Elem = T;
+
+    #[inline]
+    fn len(self) -> usize {
+        slice_len(self.as_ptr())
+    }
+
+    #[inline]
+    fn is_empty(self) -> bool {
+        StrictNonNullSlice::len(self) == 0
+    }
+
+    #[inline]
+    fn as_non_null_ptr(self) -> NonNull<T> {
+        self.cast()
+    }
+
+    #[inline]
+    fn as_mut_ptr(self) -> *mut T {
+        self.as_ptr() as *mut T
+    }
+
+    #[inline]
+    fn with_addr(self, addr: NonZeroUsize) -> Self {
+        let ptr = StrictSlice::with_addr(self.as_ptr(), addr.get());
+        // SAFETY: the address is non-zero.
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    #[inline]
+    unsafe fn get_unchecked_mut(self, index: usize) -> NonNull<T> {
+        NonNull::new_unchecked(StrictSlice::get_unchecked(self.as_ptr(), index))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn split_at(self, mid: usize) -> (Self, Self) {
+        let (left, right) = StrictSlice::split_at(self.as_ptr(), mid);
+        // SAFETY: `left` has the same address as `self`.
+        let left = unsafe { NonNull::new_unchecked(left) };
+        match NonNull::new(right) {
+            Some(right) => (left, right),
+            None => panic!("split_at wrapped around to null"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -712,6 +1639,7 @@ mod test {
     use crate::Strict;
 
     #[test]
+    #[allow(deprecated)]
     fn test_overlay() {
         let null_ptr = core::ptr::null_mut::<u8>();
         let ptr = crate::invalid_mut::<u8>(0);
@@ -741,6 +1669,261 @@ mod test {
 
         assert_eq!(x, 7 * 3 * 5 * 13 * 17);
     }
+
+    #[test]
+    fn test_bits() {
+        assert_eq!(crate::ADDR_BITS as usize, core::mem::size_of::<usize>() * 8);
+        if cfg!(sptr_simulate_wide_ptr) {
+            assert_eq!(crate::PTR_BITS, crate::ADDR_BITS * 2);
+        } else {
+            assert_eq!(
+                crate::PTR_BITS as usize,
+                core::mem::size_of::<*const u8>() * 8
+            );
+        }
+        #[cfg(feature = "uptr")]
+        {
+            assert_eq!(crate::uptr::BITS, crate::ADDR_BITS);
+            assert_eq!(crate::iptr::PTR_BITS, crate::PTR_BITS);
+        }
+    }
+
+    #[test]
+    fn test_distance() {
+        use crate::StrictNonNull;
+        use core::ptr::NonNull;
+
+        let arr = [0u32; 8];
+        let start = arr.as_ptr();
+        let end = start.wrapping_add(8);
+        unsafe {
+            // Spelled out, since newer toolchains would otherwise resolve to std's methods.
+            assert_eq!(Strict::offset_from_unsigned(end, start), 8);
+            assert_eq!(Strict::byte_offset_from_unsigned(end, start), 32);
+            assert_eq!(
+                Strict::byte_offset_from_unsigned(start.wrapping_add(3), &arr),
+                12
+            );
+            assert_eq!(Strict::offset_from_unsigned(start as *mut u32, start), 0);
+        }
+        assert_eq!(end.addr_distance(start), 32);
+        assert_eq!(start.addr_distance(end), 32usize.wrapping_neg());
+
+        let nn_start = NonNull::from(&arr[0]);
+        let nn_end = NonNull::from(&arr[5]);
+        unsafe {
+            assert_eq!(StrictNonNull::offset_from_unsigned(nn_end, nn_start), 5);
+            assert_eq!(
+                StrictNonNull::byte_offset_from_unsigned(nn_end, nn_start),
+                20
+            );
+        }
+        assert_eq!(nn_start.addr_distance(nn_end), 20usize.wrapping_neg());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "less than origin")]
+    fn test_distance_unordered_panics() {
+        let arr = [0u8; 4];
+        let start = arr.as_ptr();
+        let _ = unsafe { Strict::offset_from_unsigned(start, start.wrapping_add(1)) };
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "byte_offset_from_unsigned called with self")]
+    fn test_byte_distance_unordered_panics() {
+        let mut arr = [0u8; 4];
+        let start = arr.as_mut_ptr();
+        let _ = unsafe { Strict::byte_offset_from_unsigned(start, start.wrapping_add(1)) };
+    }
+
+    #[test]
+    #[cfg(feature = "const_fn")]
+    fn test_const_fns() {
+        const ADDR: usize = crate::const_addr(crate::invalid::<u32>(0x1000));
+        const MOVED: *const u32 = crate::const_with_addr(crate::invalid(0x1000), 0x1010);
+        const MOVED_MUT: *mut u32 = crate::const_with_addr_mut(crate::invalid_mut(0x1010), 8);
+        assert_eq!(ADDR, 0x1000);
+        assert_eq!(MOVED.addr(), 0x1010);
+        assert_eq!(MOVED_MUT.addr(), 8);
+
+        static VALUES: [u32; 2] = [1, 2];
+        static SECOND: Wrapper = Wrapper(crate::const_wrapping_byte_add(&VALUES[0], 4));
+        struct Wrapper(*const u32);
+        unsafe impl Sync for Wrapper {}
+        assert_eq!(SECOND.0, &VALUES[1] as *const u32);
+        assert_eq!(unsafe { *SECOND.0 }, 2);
+
+        static FIRST: Wrapper = Wrapper(crate::from_ref(&VALUES[0]));
+        assert_eq!(FIRST.0, VALUES.as_ptr());
+    }
+
+    #[test]
+    fn test_const_fns_at_runtime() {
+        // At runtime they work on any pointer.
+        let mut x = 3u32;
+        let ptr = &mut x as *mut u32;
+        assert_eq!(crate::const_addr(ptr), ptr.addr());
+        let tagged = crate::const_wrapping_byte_add_mut(ptr, 1);
+        let untagged = crate::const_with_addr_mut(tagged, ptr.addr());
+        unsafe { *untagged += 1 };
+        assert_eq!(x, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "slice_ptr")]
+    fn test_slice_ptrs() {
+        use crate::{StrictNonNullSlice, StrictSlice};
+        use core::num::NonZeroUsize;
+        use core::ptr::NonNull;
+
+        // Go through the traits explicitly, since newer compilers have most of these.
+        let mut arr = [1u32, 2, 3, 4, 5];
+        let whole = &mut arr[..] as *mut [u32];
+        assert_eq!(StrictSlice::len(whole), 5);
+        assert!(!StrictSlice::is_empty(whole));
+        assert_eq!(StrictSlice::as_ptr(whole), arr.as_mut_ptr());
+
+        let (left, right) = StrictSlice::split_at(whole, 2);
+        assert_eq!((StrictSlice::len(left), StrictSlice::len(right)), (2, 3));
+        unsafe { *StrictSlice::get_unchecked(right, 1) += 10 };
+
+        // Rebasing keeps the length and provenance.
+        let moved = StrictSlice::with_addr(left, StrictSlice::as_ptr(right).addr());
+        assert_eq!(StrictSlice::len(moved), 2);
+        unsafe { *StrictSlice::get_unchecked(moved, 0) += 100 };
+        assert_eq!(arr, [1, 2, 103, 14, 5]);
+
+        let shared = &arr[1..] as *const [u32];
+        assert_eq!(
+            StrictSlice::split_at(shared, 4).1 as *const u32,
+            arr.as_ptr().wrapping_add(5)
+        );
+        assert!(StrictSlice::is_empty(StrictSlice::split_at(shared, 4).1));
+
+        // Dangling slice pointers have lengths too, but null ones are always empty.
+        let null = core::ptr::slice_from_raw_parts(core::ptr::null::<u64>(), 7);
+        assert_eq!(StrictSlice::len(null), 0);
+        assert_eq!(
+            StrictSlice::len(core::ptr::slice_from_raw_parts(crate::invalid::<u8>(3), 9)),
+            9
+        );
+        assert!(StrictSlice::is_empty(core::ptr::slice_from_raw_parts(
+            core::ptr::null::<u8>(),
+            0
+        )));
+
+        let nn = NonNull::from(&mut arr[..]);
+        assert_eq!(StrictNonNullSlice::len(nn), 5);
+        assert_eq!(nn.as_non_null_ptr(), NonNull::from(&mut arr[0]));
+        let (_, tail) = StrictNonNullSlice::split_at(nn, 3);
+        let addr = NonZeroUsize::new(tail.as_mut_ptr().addr()).unwrap();
+        let rebased = StrictNonNullSlice::with_addr(nn, addr);
+        unsafe { *rebased.get_unchecked_mut(1).as_ptr() = 0 };
+        assert_eq!(arr, [1, 2, 103, 14, 0]);
+    }
+
+    #[test]
+    fn test_from_ref() {
+        use crate::StrictNonNull;
+        use core::ptr::NonNull;
+
+        let mut arr = [1u32, 2, 3];
+        let whole: *const [u32] = crate::from_ref(&arr[..]);
+        assert!(crate::addr_eq(whole, &arr[0]));
+        unsafe { *crate::from_mut(&mut arr[1]) += 10 };
+        unsafe { *<NonNull<u32> as StrictNonNull>::from_mut(&mut arr[2]).as_ptr() += 20 };
+        assert_eq!(
+            <NonNull<u32> as StrictNonNull>::from_ref(&arr[0]),
+            NonNull::from(&arr[0])
+        );
+        assert_eq!(arr, [1, 12, 23]);
+
+        let range = crate::as_ptr_range(&arr);
+        assert_eq!(range.start, arr.as_ptr());
+        assert_eq!(unsafe { *range.end.sub(1) }, 23);
+        let range = crate::as_mut_ptr_range(&mut arr[1..]);
+        unsafe { *range.end.sub(2) += 100 };
+        assert_eq!(arr, [1, 112, 23]);
+
+        let zsts = [(); 4];
+        let range = crate::as_ptr_range(&zsts);
+        assert_eq!(range.start, range.end);
+    }
+
+    #[test]
+    #[cfg(feature = "slice_ptr")]
+    #[should_panic(expected = "mid > len")]
+    fn test_slice_split_at_panics() {
+        let arr = [0u8; 4];
+        let _ = crate::StrictSlice::split_at(&arr[..] as *const [u8], 5);
+    }
+
+    #[test]
+    fn test_addr_eq() {
+        use core::fmt::{Debug, Display};
+
+        let x = 7u32;
+        let y = 7u32;
+        let x_debug: *const dyn Debug = &x;
+        let x_display: *const dyn Display = &x;
+        let y_debug: *const dyn Debug = &y;
+        assert!(crate::addr_eq(x_debug, x_display));
+        assert!(crate::addr_eq(x_debug, &x as *const u32));
+        assert!(!crate::addr_eq(x_debug, y_debug));
+
+        // Same data pointer, different vtables for the same trait.
+        let a: *const dyn Debug = &x;
+        let b: *const dyn Debug = &x as *const u32 as *const u8 as *const [u8; 4];
+        assert!(crate::addr_eq(a, b));
+
+        // Same data pointer, different lengths.
+        let arr = [1u8, 2, 3, 4];
+        let long: *const [u8] = &arr[..];
+        let short: *const [u8] = &arr[..2];
+        assert!(crate::addr_eq(long, short));
+        assert!(!crate::addr_eq(long, &arr[1..]));
+    }
+
+    #[test]
+    #[cfg(feature = "opaque_fn")]
+    fn test_code_addr() {
+        fn foo() {}
+        fn bar() {}
+
+        let foo_ptr = unsafe { crate::OpaqueFnPtr::from_fn(foo as fn()) };
+        let bar_ptr = unsafe { crate::OpaqueFnPtr::from_fn(bar as fn()) };
+        let foo_addr = foo_ptr.addr();
+        let bar_addr = bar_ptr.addr();
+
+        assert_eq!(foo_addr, foo_ptr.addr());
+        assert_eq!(foo_addr < bar_addr, foo_ptr.addr() < bar_ptr.addr());
+        assert_eq!((foo_addr + 4) - foo_addr, 4);
+        assert_eq!((foo_addr + 4) - 4, foo_addr);
+        assert_eq!(foo_addr.checked_sub(usize::MAX), None);
+        assert_eq!(
+            foo_addr.wrapping_add(usize::MAX).wrapping_sub(usize::MAX),
+            foo_addr
+        );
+        assert_eq!(foo_addr < bar_addr, foo_ptr < bar_ptr);
+    }
+
+    #[test]
+    #[cfg(feature = "opaque_fn")]
+    fn test_fn_addr_eq() {
+        fn foo() {}
+        fn bar(_: u32) -> u32 {
+            0
+        }
+
+        let foo_ptr = unsafe { crate::OpaqueFnPtr::from_fn(foo as fn()) };
+        let foo_ptr2 = unsafe { crate::OpaqueFnPtr::from_fn(foo as fn()) };
+        let bar_ptr = unsafe { crate::OpaqueFnPtr::from_fn(bar as fn(u32) -> u32) };
+        assert!(crate::fn_addr_eq(foo_ptr, foo_ptr2));
+        assert!(!crate::fn_addr_eq(foo_ptr, bar_ptr));
+    }
 }
 
 #[cfg(feature = "uptr")]
@@ -753,4 +1936,83 @@ pub use self::int::uptr;
 #[cfg(feature = "opaque_fn")]
 pub mod func;
 #[cfg(feature = "opaque_fn")]
+pub use self::func::fn_addr_eq;
+#[cfg(feature = "opaque_fn")]
+pub use self::func::CodeAddr;
+#[cfg(feature = "opaque_fn")]
 pub use self::func::OpaqueFnPtr;
+
+#[cfg(feature = "rel_ptr")]
+pub mod rel;
+#[cfg(feature = "rel_ptr")]
+pub use self::rel::RelPtr;
+
+#[cfg(any(feature = "compressed", feature = "swizzle"))]
+mod region;
+
+#[cfg(feature = "compressed")]
+pub mod compressed;
+#[cfg(feature = "compressed")]
+pub use self::compressed::CompressedPtr;
+#[cfg(feature = "compressed")]
+pub use self::compressed::Region;
+
+#[cfg(feature = "swizzle")]
+pub mod swizzle;
+
+#[cfg(feature = "expose_tracking")]
+pub mod expose;
+
+#[cfg(feature = "cap")]
+pub mod cap;
+#[cfg(feature = "cap")]
+pub use self::cap::Cap;
+
+#[cfg(feature = "provenance")]
+pub mod provenance;
+#[cfg(feature = "provenance")]
+pub use self::provenance::Provenance;
+#[cfg(feature = "provenance")]
+pub use self::provenance::ProvenanceArena;
+
+#[cfg(feature = "ptr_range")]
+pub mod range;
+#[cfg(feature = "ptr_range")]
+pub use self::range::PtrRange;
+
+#[cfg(feature = "ptr_key")]
+pub mod key;
+#[cfg(feature = "ptr_key")]
+pub use self::key::PtrKey;
+
+#[cfg(feature = "ptr_or_int")]
+pub mod ptr_or_int;
+#[cfg(feature = "ptr_or_int")]
+pub use self::ptr_or_int::PtrOrInt;
+
+#[cfg(all(feature = "nan_box", target_pointer_width = "64"))]
+pub mod nan_box;
+#[cfg(all(feature = "nan_box", target_pointer_width = "64"))]
+pub use self::nan_box::NanBox;
+
+#[cfg(feature = "xor_ptr")]
+pub mod xor;
+#[cfg(feature = "xor_ptr")]
+pub use self::xor::MangledPtr;
+#[cfg(feature = "xor_ptr")]
+pub use self::xor::XorPtr;
+
+#[cfg(feature = "atomic")]
+pub mod atomic;
+#[cfg(feature = "atomic")]
+pub use self::atomic::AtomicPtrWithCounter;
+#[cfg(feature = "atomic")]
+pub use self::atomic::AtomicTaggedPtr;
+
+#[cfg(feature = "ptr_meta")]
+pub mod meta;
+#[cfg(feature = "ptr_meta")]
+pub use self::meta::{
+    from_raw_parts, from_raw_parts_mut, metadata, to_raw_parts, to_raw_parts_mut, DynMetadata,
+    Pointee,
+};
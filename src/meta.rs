@@ -0,0 +1,381 @@
+//! Pointer metadata -- [Pointee][], [to_raw_parts][], [from_raw_parts][].
+//!
+//! This polyfills the unstable `ptr_metadata` feature: splitting a (possibly fat) pointer
+//! into a thin data pointer and its metadata, and putting it back together. The data
+//! pointer keeps the provenance of the original pointer, and [`from_raw_parts`] gives the
+//! rebuilt pointer the provenance of the data pointer, so this is how you change the
+//! address of an unsized pointer without losing either:
+//!
+//! ```
+//! #![allow(unstable_name_collisions)]
+//! use sptr::Strict;
+//!
+//! let mut arr = [1u32, 2, 3, 4];
+//! let first_half = core::ptr::slice_from_raw_parts_mut(arr.as_mut_ptr(), 2);
+//!
+//! let (data, len) = sptr::to_raw_parts_mut(first_half);
+//! let second_half: *mut [u32] = sptr::from_raw_parts_mut(data.map_addr(|a| a + 8), len);
+//! unsafe { (*second_half)[1] += 10 };
+//! assert_eq!(arr, [1, 2, 3, 14]);
+//! ```
+//!
+//! Slices and `str` have their length as metadata, and `Sized` types have `()`. Trait
+//! objects have a [`DynMetadata`], but since there's no stable way to be generic over them,
+//! [`Pointee`] is only implemented for `dyn Any` (and `+ Send`/`+ Sync`), `dyn Debug` and
+//! `dyn Display`. Use [`dyn_pointee!`][crate::dyn_pointee] to implement it for your own
+//! traits.
+//!
+//! Requires 1.42 (for `core::ptr::slice_from_raw_parts`).
+
+use crate::private::RawParts;
+#[allow(unused_imports)]
+use crate::Strict;
+use core::any::Any;
+use core::fmt::{Debug, Display};
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+
+/// The types that pointers can point to, and the metadata a pointer to them carries.
+///
+/// This is a polyfill of `core::ptr::Pointee`. It's implemented for all `Sized` types, for
+/// slices and `str`, and for some trait objects (see the [module docs][crate::meta]).
+///
+/// # Safety
+///
+/// This should only be implemented with [`dyn_pointee!`][crate::dyn_pointee].
+pub unsafe trait Pointee {
+    /// The metadata of a pointer to `Self`.
+    type Metadata: Copy + Send + Sync + Ord + Hash + Unpin + Debug;
+
+    #[doc(hidden)]
+    fn __metadata(ptr: *const Self) -> Self::Metadata;
+
+    #[doc(hidden)]
+    fn __from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self;
+}
+
+unsafe impl<T> Pointee for T {
+    type Metadata = ();
+
+    #[inline]
+    fn __metadata(ptr: *const Self) -> Self::Metadata {
+        RawParts::metadata(ptr)
+    }
+
+    #[inline]
+    fn __from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+        RawParts::from_raw_parts(data, meta)
+    }
+}
+
+unsafe impl<T> Pointee for [T] {
+    type Metadata = usize;
+
+    #[inline]
+    fn __metadata(ptr: *const Self) -> Self::Metadata {
+        RawParts::metadata(ptr)
+    }
+
+    #[inline]
+    fn __from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+        RawParts::from_raw_parts(data, meta)
+    }
+}
+
+unsafe impl Pointee for str {
+    type Metadata = usize;
+
+    #[inline]
+    fn __metadata(ptr: *const Self) -> Self::Metadata {
+        RawParts::metadata(ptr)
+    }
+
+    #[inline]
+    fn __from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+        RawParts::from_raw_parts(data, meta)
+    }
+}
+
+/// The metadata of a pointer to the trait object `Dyn`: a pointer to its vtable.
+///
+/// Two `DynMetadata` are equal if they have the same vtable address. Like the trait
+/// objects themselves, the same type can have several vtables, so this shouldn't be relied
+/// on to tell types apart.
+pub struct DynMetadata<Dyn: ?Sized> {
+    vtable: *const (),
+    _marker: PhantomData<*const Dyn>,
+}
+
+// The vtable is immutable, and only ever used to rebuild a `*const Dyn`.
+unsafe impl<Dyn: ?Sized> Send for DynMetadata<Dyn> {}
+unsafe impl<Dyn: ?Sized> Sync for DynMetadata<Dyn> {}
+
+/// The two halves of a trait object pointer.
+///
+/// There's no stable way to get at these, so this assumes the layout every version of
+/// Rust so far has used: the data pointer, then the vtable pointer.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DynParts {
+    data: *const (),
+    vtable: *const (),
+}
+
+#[repr(C)]
+union DynRepr<Dyn: ?Sized> {
+    ptr: *const Dyn,
+    parts: DynParts,
+}
+
+impl<Dyn: ?Sized> DynMetadata<Dyn> {
+    /// 1 if `*const Dyn` can't be a `DynParts`, for a compile-time check in
+    /// [`dyn_pointee!`][crate::dyn_pointee].
+    #[doc(hidden)]
+    pub const __BAD_LAYOUT: usize =
+        (core::mem::size_of::<*const Dyn>() != core::mem::size_of::<DynParts>()) as usize;
+
+    /// # Safety
+    ///
+    /// `Dyn` must be a trait object type.
+    #[doc(hidden)]
+    #[inline]
+    pub unsafe fn __of(ptr: *const Dyn) -> Self {
+        debug_assert_eq!(
+            core::mem::size_of::<*const Dyn>(),
+            core::mem::size_of::<DynParts>()
+        );
+        // SAFETY: see `DynParts`.
+        let vtable = DynRepr { ptr }.parts.vtable;
+        DynMetadata {
+            vtable,
+            _marker: PhantomData,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `Dyn` must be a trait object type.
+    #[doc(hidden)]
+    #[inline]
+    pub unsafe fn __with_data(self, data: *const ()) -> *const Dyn {
+        let parts = DynParts {
+            data,
+            vtable: self.vtable,
+        };
+        // SAFETY: see `DynParts`. The data pointer is copied as a pointer, so it keeps
+        // its provenance.
+        DynRepr { parts }.ptr
+    }
+}
+
+/// Implements [`Pointee`] for trait objects, with [`DynMetadata`] as their metadata.
+///
+/// # Safety
+///
+/// Every type must be a trait object (`dyn Trait`, optionally with auto traits and a
+/// lifetime). The metadata is read out of the pointer assuming the layout every version of
+/// Rust so far has used for trait object pointers: the data pointer, then the vtable
+/// pointer. This is checked at compile time as far as it can be, which is only that the
+/// pointer is two pointers wide, so this must not be used on custom DSTs like
+/// `struct Foo([u8])`.
+///
+/// ```
+/// trait Shape {
+///     fn area(&self) -> f64;
+/// }
+/// // SAFETY: these are trait objects.
+/// sptr::dyn_pointee!(unsafe { dyn Shape, dyn Shape + Send });
+///
+/// struct Square(f64);
+/// impl Shape for Square {
+///     fn area(&self) -> f64 {
+///         self.0 * self.0
+///     }
+/// }
+///
+/// let shapes = [Square(1.0), Square(2.0)];
+/// let (data, vtable) = sptr::to_raw_parts(shapes.as_ptr() as *const dyn Shape);
+/// let next = (data as *const Square).wrapping_add(1) as *const ();
+/// let second: *const dyn Shape = sptr::from_raw_parts(next, vtable);
+/// assert_eq!(unsafe { (*second).area() }, 4.0);
+/// ```
+#[macro_export]
+macro_rules! dyn_pointee {
+    (unsafe { $($dyn_ty: ty),* $(,)? }) => {
+        $(
+            const _: [(); 0] = [(); $crate::DynMetadata::<$dyn_ty>::__BAD_LAYOUT];
+
+            unsafe impl $crate::Pointee for $dyn_ty {
+                type Metadata = $crate::DynMetadata<$dyn_ty>;
+
+                // `ptr` is never dereferenced, only split into its two halves.
+                #[inline]
+                #[allow(clippy::not_unsafe_ptr_arg_deref)]
+                fn __metadata(ptr: *const Self) -> Self::Metadata {
+                    // SAFETY: the caller promised `Self` is a trait object.
+                    unsafe { $crate::DynMetadata::__of(ptr) }
+                }
+
+                #[inline]
+                fn __from_raw_parts(data: *const (), meta: Self::Metadata) -> *const Self {
+                    // SAFETY: the caller promised `Self` is a trait object.
+                    unsafe { meta.__with_data(data) }
+                }
+            }
+        )*
+    };
+}
+
+// SAFETY: these are trait objects.
+dyn_pointee!(unsafe {
+    dyn Any,
+    dyn Any + Send,
+    dyn Any + Send + Sync,
+    dyn Debug,
+    dyn Display,
+});
+
+/// Gets the metadata of a pointer.
+///
+/// This is a polyfill of `core::ptr::metadata`, except that slice (and `str`) pointers
+/// with a null data pointer have length 0, since there's no way to read their length on
+/// older compilers.
+#[inline]
+#[must_use]
+pub fn metadata<T: ?Sized + Pointee>(ptr: *const T) -> T::Metadata {
+    T::__metadata(ptr)
+}
+
+/// Splits a pointer into its data pointer and metadata.
+///
+/// The data pointer has the same address and provenance as `ptr`. This is a polyfill of
+/// `pointer::to_raw_parts`.
+#[inline]
+#[must_use]
+pub fn to_raw_parts<T: ?Sized + Pointee>(ptr: *const T) -> (*const (), T::Metadata) {
+    (ptr as *const (), metadata(ptr))
+}
+
+/// Splits a mutable pointer into its data pointer and metadata.
+///
+/// See [`to_raw_parts`].
+#[inline]
+#[must_use]
+pub fn to_raw_parts_mut<T: ?Sized + Pointee>(ptr: *mut T) -> (*mut (), T::Metadata) {
+    (ptr as *mut (), metadata(ptr))
+}
+
+/// Builds a (possibly fat) pointer from a data pointer and metadata.
+///
+/// The result has the address and provenance of `data`. This is a polyfill of
+/// `core::ptr::from_raw_parts`.
+#[inline]
+#[must_use]
+pub fn from_raw_parts<T: ?Sized + Pointee>(data: *const (), meta: T::Metadata) -> *const T {
+    T::__from_raw_parts(data, meta)
+}
+
+/// Builds a (possibly fat) mutable pointer from a data pointer and metadata.
+///
+/// See [`from_raw_parts`].
+#[inline]
+#[must_use]
+pub fn from_raw_parts_mut<T: ?Sized + Pointee>(data: *mut (), meta: T::Metadata) -> *mut T {
+    T::__from_raw_parts(data, meta) as *mut T
+}
+
+// Manual impls to avoid requiring `Dyn: Clone` and friends.
+impl<Dyn: ?Sized> Copy for DynMetadata<Dyn> {}
+impl<Dyn: ?Sized> Clone for DynMetadata<Dyn> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Dyn: ?Sized> PartialEq for DynMetadata<Dyn> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.vtable == other.vtable
+    }
+}
+impl<Dyn: ?Sized> Eq for DynMetadata<Dyn> {}
+
+impl<Dyn: ?Sized> PartialOrd for DynMetadata<Dyn> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<Dyn: ?Sized> Ord for DynMetadata<Dyn> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.vtable.addr().cmp(&other.vtable.addr())
+    }
+}
+
+impl<Dyn: ?Sized> Hash for DynMetadata<Dyn> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.vtable.addr());
+    }
+}
+
+impl<Dyn: ?Sized> Debug for DynMetadata<Dyn> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("DynMetadata").field(&self.vtable).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #[allow(unused_imports)]
+    use crate::Strict;
+    use core::any::Any;
+    use core::fmt::Debug;
+
+    #[test]
+    fn test_thin_and_slices() {
+        let mut x = 5u64;
+        let (data, ()) = crate::to_raw_parts_mut(&mut x as *mut u64);
+        unsafe { *crate::from_raw_parts_mut::<u64>(data, ()) += 1 };
+        assert_eq!(x, 6);
+
+        let arr = [1u16, 2, 3];
+        let tail = core::ptr::slice_from_raw_parts(arr.as_ptr().wrapping_add(1), 2);
+        let (data, len) = crate::to_raw_parts(tail);
+        assert_eq!((data, len), (arr.as_ptr().wrapping_add(1) as *const (), 2));
+        let rebuilt: *const [u16] = crate::from_raw_parts(data.map_addr(|a| a - 2), 3);
+        assert_eq!(unsafe { &*rebuilt }, &arr);
+        assert_eq!(rebuilt as *const u16, arr.as_ptr());
+
+        let null = core::ptr::slice_from_raw_parts(core::ptr::null::<u16>(), 4);
+        assert_eq!(crate::metadata(null), 0);
+
+        let s = "hello";
+        let (data, len) = crate::to_raw_parts(s as *const str);
+        let tail: *const str = crate::from_raw_parts(data.map_addr(|a| a + 1), len - 1);
+        assert_eq!(unsafe { &*tail }, "ello");
+    }
+
+    #[test]
+    fn test_dyn() {
+        extern crate std;
+
+        let values = [10u32, 20];
+        let first = values.as_ptr() as *const dyn Debug;
+        let (data, vtable) = crate::to_raw_parts(first);
+        assert_eq!(data, values.as_ptr() as *const ());
+        assert_eq!(vtable, crate::metadata(&values[1] as *const dyn Debug));
+
+        let second: *const dyn Debug = crate::from_raw_parts(data.map_addr(|a| a + 4), vtable);
+        assert_eq!(std::format!("{:?}", unsafe { &*second }), "20");
+
+        let mut y = 7i8;
+        let any: *mut dyn Any = &mut y;
+        let (data, vtable) = crate::to_raw_parts_mut(any);
+        let rebuilt: *mut dyn Any = crate::from_raw_parts_mut(data, vtable);
+        unsafe { *(*rebuilt).downcast_mut::<i8>().unwrap() += 1 };
+        assert_eq!(y, 8);
+    }
+}
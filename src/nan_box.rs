@@ -0,0 +1,223 @@
+//! NaN-boxed values that keep pointer provenance -- [NanBox][].
+//!
+//! NaN-boxing stores either an `f64` or a pointer in one 64-bit word, by hiding pointers in
+//! the payload bits of NaNs (which a float can only ever have one canonical version of).
+//! The usual implementation goes through `u64`, which means every pointer comes back out of
+//! an integer-to-pointer cast. [`NanBox`] stores the word as a `*mut ()` instead: floats
+//! are stored as [`invalid`][crate::invalid_mut] pointers, and pointers are tagged and
+//! untagged with [`map_addr`][crate::Strict::map_addr], so they keep their provenance.
+//!
+//! This is only available on 64-bit targets, and pointers must fit in 48 bits.
+//!
+//! ```
+//! use sptr::NanBox;
+//!
+//! let mut x = 1u32;
+//! let values = [NanBox::from_f64(1.5), NanBox::from_tagged_ptr(&mut x, 3)];
+//!
+//! assert_eq!(values[0].as_f64(), Some(1.5));
+//! assert_eq!(values[1].tag(), Some(3));
+//! let ptr = values[1].as_ptr().unwrap() as *mut u32;
+//! unsafe { *ptr += 1 };
+//! assert_eq!(x, 2);
+//! ```
+
+#[allow(unused_imports)]
+use crate::Strict;
+
+/// The bits that are all set in a boxed pointer: the sign bit, the exponent, and the
+/// quiet bit. No float has all of these set once NaNs are canonicalized.
+const PTR_PREFIX: usize = 0xFFF8_0000_0000_0000;
+
+/// Where the tag of a boxed pointer goes.
+const TAG_SHIFT: u32 = 48;
+
+/// The bits of a boxed pointer that hold its address.
+const ADDR_MASK: usize = (1 << TAG_SHIFT) - 1;
+
+/// The NaN that all NaNs are canonicalized to.
+const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+
+/// The largest tag a boxed pointer can have.
+pub const MAX_TAG: u8 = 7;
+
+/// A 64-bit word holding either an `f64` or a pointer with a 3-bit tag.
+///
+/// All NaNs are canonicalized when they're boxed, so the sign and payload of NaNs aren't
+/// preserved. Pointers are stored as negative quiet NaNs, with the tag in the 3 bits above
+/// the 48-bit address.
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NanBox(*mut ());
+
+impl NanBox {
+    /// Boxes a float.
+    #[inline]
+    #[must_use]
+    pub fn from_f64(value: f64) -> Self {
+        let bits = if value.is_nan() {
+            CANONICAL_NAN
+        } else {
+            value.to_bits()
+        };
+        NanBox(crate::invalid_mut(bits as usize))
+    }
+
+    /// Boxes a pointer, with the tag 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr`'s address doesn't fit in 48 bits.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn from_ptr<T>(ptr: *mut T) -> Self {
+        Self::from_tagged_ptr(ptr, 0)
+    }
+
+    /// Boxes a pointer with a tag, which can be used to tell different kinds of pointers
+    /// (or other non-float values) apart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ptr`'s address doesn't fit in 48 bits, or `tag` is greater than [`MAX_TAG`].
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn from_tagged_ptr<T>(ptr: *mut T, tag: u8) -> Self {
+        let ptr = ptr as *mut ();
+        assert!(
+            ptr.addr() & !ADDR_MASK == 0,
+            "{:p} doesn't fit in a NanBox",
+            ptr
+        );
+        assert!(
+            tag <= MAX_TAG,
+            "NanBox tag {} is greater than {}",
+            tag,
+            MAX_TAG
+        );
+        NanBox(ptr.map_addr(|a| a | PTR_PREFIX | ((tag as usize) << TAG_SHIFT)))
+    }
+
+    /// Returns `true` if this is a float.
+    #[inline]
+    #[must_use]
+    pub fn is_f64(&self) -> bool {
+        !self.is_ptr()
+    }
+
+    /// Returns `true` if this is a pointer.
+    #[inline]
+    #[must_use]
+    pub fn is_ptr(&self) -> bool {
+        self.0.addr() & PTR_PREFIX == PTR_PREFIX
+    }
+
+    /// Gets the float, if this is one.
+    #[inline]
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        if self.is_f64() {
+            Some(f64::from_bits(self.0.addr() as u64))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the pointer, with its original provenance, if this is one.
+    #[inline]
+    #[must_use]
+    pub fn as_ptr(&self) -> Option<*mut ()> {
+        if self.is_ptr() {
+            Some(self.0.map_addr(|a| a & ADDR_MASK))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the tag of the pointer, if this is one.
+    #[inline]
+    #[must_use]
+    pub fn tag(&self) -> Option<u8> {
+        if self.is_ptr() {
+            Some((self.0.addr() >> TAG_SHIFT) as u8 & MAX_TAG)
+        } else {
+            None
+        }
+    }
+}
+
+impl core::fmt::Debug for NanBox {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (self.as_f64(), self.as_ptr(), self.tag()) {
+            (Some(value), _, _) => f.debug_tuple("F64").field(&value).finish(),
+            (_, Some(ptr), Some(tag)) => f
+                .debug_struct("Ptr")
+                .field("ptr", &ptr)
+                .field("tag", &tag)
+                .finish(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NanBox, MAX_TAG};
+    #[allow(unused_imports)]
+    use crate::Strict;
+
+    #[test]
+    fn test_floats() {
+        let floats = [
+            0.0,
+            -0.0,
+            1.5,
+            -1e300,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE,
+        ];
+        for &value in &floats {
+            let boxed = NanBox::from_f64(value);
+            assert!(boxed.is_f64());
+            assert_eq!(boxed.tag(), None);
+            assert_eq!(boxed.as_f64().unwrap().to_bits(), value.to_bits());
+        }
+
+        // NaNs are canonicalized, even the ones that look like pointers.
+        let evil = f64::from_bits(0xFFFF_0000_0000_1234);
+        for &nan in &[f64::NAN, -f64::NAN, evil] {
+            let boxed = NanBox::from_f64(nan);
+            assert!(boxed.as_f64().unwrap().is_nan());
+            assert_eq!(boxed, NanBox::from_f64(f64::NAN));
+        }
+    }
+
+    #[test]
+    fn test_ptrs() {
+        let mut values = [1u64, 2];
+        let ptr = values.as_mut_ptr();
+        for tag in 0..=MAX_TAG {
+            let boxed = NanBox::from_tagged_ptr(ptr.wrapping_add(1), tag);
+            assert!(boxed.is_ptr());
+            assert_eq!(boxed.as_f64(), None);
+            assert_eq!(boxed.tag(), Some(tag));
+            let unboxed = boxed.as_ptr().unwrap() as *mut u64;
+            assert_eq!(unboxed, ptr.wrapping_add(1));
+            unsafe { *unboxed += 1 };
+        }
+        assert_eq!(values, [1, 2 + MAX_TAG as u64 + 1]);
+
+        let null = NanBox::from_ptr(core::ptr::null_mut::<u8>());
+        assert_eq!(null.as_ptr(), Some(core::ptr::null_mut()));
+        assert_eq!(null.tag(), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't fit")]
+    fn test_big_ptr_panics() {
+        let _ = NanBox::from_ptr(crate::invalid_mut::<u8>(1 << 48));
+    }
+}
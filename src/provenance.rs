@@ -0,0 +1,464 @@
+//! Carrying provenance separately from addresses -- [Provenance][] and [ProvenanceArena][].
+//!
+//! A common pattern is to split a pointer into "which allocation" and "where in it", and only
+//! store the second part (as an index, a `u32` offset, an address in some hash table...). The
+//! tempting way to get the pointer back is `(base as usize + offset) as *mut T`, which loses
+//! the provenance. The right way is to hold onto *some* pointer into the allocation and use
+//! [`with_addr`][crate::Strict::with_addr] on it, and [`Provenance`] makes that pointer a
+//! first-class thing that can't be accidentally dereferenced or mixed up with the others.
+//!
+//! ```
+//! use sptr::Provenance;
+//!
+//! let mut nodes = [10u32, 20, 30, 40];
+//! let prov = Provenance::from_slice_mut(&mut nodes);
+//!
+//! // Only store the index...
+//! let index: u32 = 2;
+//!
+//! // ...and get the pointer back later, with the slice's provenance.
+//! let ptr = prov.at_offset(index as usize);
+//! unsafe { *ptr += 1 };
+//! assert_eq!(nodes[2], 31);
+//! ```
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::marker::PhantomData;
+
+/// The provenance of a pointer, without a usable address.
+///
+/// A `Provenance` can't be dereferenced, only used to make new pointers with
+/// [`at`][Provenance::at] (from an address) or [`at_offset`][Provenance::at_offset]
+/// (from an index). If it was created from a slice, it remembers the slice's length and
+/// those methods check that the new pointer is in bounds (or one past the end).
+///
+/// The lifetime `'a` ties the `Provenance` to the borrow it was created from, so that it can't
+/// outlive it by accident (the pointers it makes still can, because they're raw pointers).
+pub struct Provenance<'a, T> {
+    ptr: *mut T,
+    len: Option<usize>,
+    _marker: PhantomData<&'a mut [T]>,
+}
+
+/// The error returned when trying to make a pointer outside of a bounded [`Provenance`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OutOfBounds;
+
+impl core::fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "address is out of bounds of the provenance")
+    }
+}
+
+impl<'a, T> Provenance<'a, T> {
+    /// Takes the provenance of `ptr`, without any bounds.
+    ///
+    /// This is always safe, because all it can do is make raw pointers, and it's on you to
+    /// only dereference the ones that are actually allowed by `ptr`'s provenance.
+    #[inline]
+    #[must_use]
+    pub fn from_ptr(ptr: *mut T) -> Self {
+        Provenance {
+            ptr,
+            len: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Takes the provenance of `ptr`, without any bounds.
+    ///
+    /// Pointers made from this `Provenance` must not be written through.
+    #[inline]
+    #[must_use]
+    pub fn from_const_ptr(ptr: *const T) -> Self {
+        Self::from_ptr(ptr as *mut T)
+    }
+
+    /// Takes the provenance of the given slice, bounded to it.
+    #[inline]
+    #[must_use]
+    pub fn from_slice_mut(slice: &'a mut [T]) -> Self {
+        Provenance {
+            len: Some(slice.len()),
+            ptr: slice.as_mut_ptr(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Takes the provenance of the given slice, bounded to it.
+    ///
+    /// Pointers made from this `Provenance` must not be written through.
+    #[inline]
+    #[must_use]
+    pub fn from_slice(slice: &'a [T]) -> Self {
+        Provenance {
+            len: Some(slice.len()),
+            ptr: slice.as_ptr() as *mut T,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Takes the provenance of `ptr`, bounded to the `len` elements starting at it.
+    ///
+    /// This is safe for the same reason as [`from_ptr`][Provenance::from_ptr]: the bounds
+    /// are only a debugging aid, and don't make the pointers any more valid.
+    #[inline]
+    #[must_use]
+    pub fn from_raw_parts(ptr: *mut T, len: usize) -> Self {
+        Provenance {
+            ptr,
+            len: Some(len),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Gets the address of the pointer this was created from.
+    ///
+    /// Offsets passed to [`at_offset`][Provenance::at_offset] are relative to this.
+    #[inline]
+    #[must_use]
+    pub fn base_addr(&self) -> usize {
+        self.ptr.addr()
+    }
+
+    /// Gets the number of elements this is bounded to, if any.
+    #[inline]
+    #[must_use]
+    pub fn bound(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Returns `true` if [`at`][Provenance::at] would accept `addr`.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, addr: usize) -> bool {
+        self.try_at(addr).is_ok()
+    }
+
+    /// Makes a pointer to `addr`, with this provenance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this `Provenance` is bounded and `addr` isn't within its elements (or one
+    /// past the end). Unbounded `Provenance`s accept any address.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn at(&self, addr: usize) -> *mut T {
+        match self.try_at(addr) {
+            Ok(ptr) => ptr,
+            Err(_) => panic!(
+                "address {:#x} is out of bounds of the provenance of {:p}",
+                addr, self.ptr
+            ),
+        }
+    }
+
+    /// Makes a pointer to the element `offset` elements past the base, with this provenance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this `Provenance` is bounded and `offset` is greater than its length.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn at_offset(&self, offset: usize) -> *mut T {
+        match self.try_at_offset(offset) {
+            Ok(ptr) => ptr,
+            Err(_) => panic!(
+                "offset {} is out of bounds of the provenance of {:p} (length {:?})",
+                offset, self.ptr, self.len
+            ),
+        }
+    }
+
+    /// Makes a pointer to `addr`, with this provenance, or fails if it's out of bounds.
+    ///
+    /// The address doesn't have to be aligned to an element, since you may be pointing at
+    /// a field, or about to cast the pointer to something else.
+    #[inline]
+    pub fn try_at(&self, addr: usize) -> Result<*mut T, OutOfBounds> {
+        if let Some(len) = self.len {
+            let offset = addr.checked_sub(self.base_addr()).ok_or(OutOfBounds)?;
+            if offset > len.saturating_mul(core::mem::size_of::<T>()) {
+                return Err(OutOfBounds);
+            }
+        }
+        Ok(self.ptr.with_addr(addr))
+    }
+
+    /// Makes a pointer to the element `offset` elements past the base, with this provenance,
+    /// or fails if it's out of bounds.
+    #[inline]
+    pub fn try_at_offset(&self, offset: usize) -> Result<*mut T, OutOfBounds> {
+        match self.len {
+            Some(len) if offset > len => return Err(OutOfBounds),
+            _ => {}
+        }
+        let bytes = offset.wrapping_mul(core::mem::size_of::<T>());
+        Ok(self.ptr.map_addr(|a| a.wrapping_add(bytes)))
+    }
+
+    /// Gets the offset of `ptr` from the base, in elements, if it's in bounds and aligned
+    /// to an element.
+    ///
+    /// This is the inverse of [`at_offset`][Provenance::at_offset].
+    #[inline]
+    #[must_use]
+    pub fn offset_of(&self, ptr: *const T) -> Option<usize> {
+        let bytes = ptr.addr().checked_sub(self.base_addr())?;
+        let offset = match core::mem::size_of::<T>() {
+            0 => 0,
+            size if bytes % size == 0 => bytes / size,
+            _ => return None,
+        };
+        match self.len {
+            Some(len) if offset > len => None,
+            _ => Some(offset),
+        }
+    }
+}
+
+// Manual impls to avoid requiring `T: Clone` and friends.
+impl<'a, T> Copy for Provenance<'a, T> {}
+impl<'a, T> Clone for Provenance<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> core::fmt::Debug for Provenance<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Provenance")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Converts between indices into a slice and pointers to its elements, all derived from
+/// the slice's pointer.
+///
+/// This is for slab allocators, ECS storages, and anything else that keeps its elements
+/// in one buffer and hands out `usize` handles to them. Handles can be turned into pointers
+/// with [`ptr_of`][ProvenanceArena::ptr_of] and back with
+/// [`index_of`][ProvenanceArena::index_of], without any integer-to-pointer casts.
+///
+/// Unlike a bounded [`Provenance`], this only deals in pointers to actual elements, never
+/// one-past-the-end.
+///
+/// ```
+/// use sptr::ProvenanceArena;
+///
+/// let mut storage = vec![0u64; 16];
+/// let arena = ProvenanceArena::from_slice_mut(&mut storage);
+///
+/// let handle: usize = 7;
+/// let ptr = arena.ptr_of(handle);
+/// unsafe { *ptr = 42 };
+/// assert_eq!(arena.index_of(ptr), Some(handle));
+/// assert_eq!(storage[7], 42);
+/// ```
+pub struct ProvenanceArena<'a, T> {
+    prov: Provenance<'a, T>,
+    len: usize,
+}
+
+impl<'a, T> ProvenanceArena<'a, T> {
+    /// Creates an arena over the elements of the given slice.
+    #[inline]
+    #[must_use]
+    pub fn from_slice_mut(slice: &'a mut [T]) -> Self {
+        ProvenanceArena {
+            len: slice.len(),
+            prov: Provenance::from_slice_mut(slice),
+        }
+    }
+
+    /// Creates an arena over the elements of the given slice.
+    ///
+    /// Pointers made from this arena must not be written through.
+    #[inline]
+    #[must_use]
+    pub fn from_slice(slice: &'a [T]) -> Self {
+        ProvenanceArena {
+            len: slice.len(),
+            prov: Provenance::from_slice(slice),
+        }
+    }
+
+    /// Creates an arena over the `len` elements starting at `ptr`.
+    ///
+    /// This is safe for the same reason as [`Provenance::from_raw_parts`]: the arena only
+    /// makes raw pointers, and it's on you to only dereference the ones that `ptr`'s
+    /// provenance actually allows.
+    #[inline]
+    #[must_use]
+    pub fn from_raw_parts(ptr: *mut T, len: usize) -> Self {
+        ProvenanceArena {
+            len,
+            prov: Provenance::from_raw_parts(ptr, len),
+        }
+    }
+
+    /// Gets the number of elements in the arena.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the arena has no elements.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the provenance of the whole arena.
+    #[inline]
+    #[must_use]
+    pub fn provenance(&self) -> Provenance<'a, T> {
+        self.prov
+    }
+
+    /// Gets a pointer to the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn ptr_of(&self, index: usize) -> *mut T {
+        match self.get_ptr(index) {
+            Some(ptr) => ptr,
+            None => panic!(
+                "index {} is out of bounds of an arena of length {}",
+                index, self.len
+            ),
+        }
+    }
+
+    /// Gets a pointer to the element at `index`, or `None` if it's out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get_ptr(&self, index: usize) -> Option<*mut T> {
+        if index < self.len {
+            self.prov.try_at_offset(index).ok()
+        } else {
+            None
+        }
+    }
+
+    /// Gets the index of the element that `ptr` points to.
+    ///
+    /// Returns `None` if `ptr` doesn't point to the start of one of the arena's elements.
+    /// For zero-sized `T`, every element has the same address, so this can only ever
+    /// return `Some(0)`.
+    #[inline]
+    #[must_use]
+    pub fn index_of(&self, ptr: *const T) -> Option<usize> {
+        self.prov.offset_of(ptr).filter(|&index| index < self.len)
+    }
+
+    /// Returns `true` if `ptr` points to the start of one of the arena's elements.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, ptr: *const T) -> bool {
+        self.index_of(ptr).is_some()
+    }
+}
+
+impl<'a, T> Copy for ProvenanceArena<'a, T> {}
+impl<'a, T> Clone for ProvenanceArena<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> core::fmt::Debug for ProvenanceArena<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ProvenanceArena")
+            .field("ptr", &self.prov.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OutOfBounds, Provenance, ProvenanceArena};
+    #[allow(unused_imports)]
+    use crate::Strict;
+
+    #[test]
+    fn test_bounded() {
+        let mut arr = [1u32, 2, 3, 4];
+        let prov = Provenance::from_slice_mut(&mut arr);
+        let base = prov.base_addr();
+
+        unsafe {
+            *prov.at(base + 4) += 10;
+            *prov.at_offset(3) += 20;
+        }
+        assert_eq!(prov.offset_of(prov.at_offset(3)), Some(3));
+        assert_eq!(prov.offset_of(prov.at(base + 2)), None);
+
+        // One past the end is fine, further isn't.
+        assert!(prov.try_at_offset(4).is_ok());
+        assert_eq!(prov.try_at_offset(5), Err(OutOfBounds));
+        assert!(prov.contains(base + 16));
+        assert!(!prov.contains(base + 17));
+        assert!(!prov.contains(base - 1));
+        assert_eq!(arr, [1, 12, 3, 24]);
+    }
+
+    #[test]
+    fn test_unbounded() {
+        let x = 5u64;
+        let prov = Provenance::from_const_ptr(&x);
+        assert_eq!(prov.bound(), None);
+        assert!(prov.try_at_offset(1000).is_ok());
+        assert_eq!(prov.at(prov.base_addr()).addr(), (&x as *const u64).addr());
+        assert_eq!(unsafe { *prov.at_offset(0) }, 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_out_of_bounds_panics() {
+        let arr = [0u8; 4];
+        let prov = Provenance::from_slice(&arr);
+        let _ = prov.at_offset(5);
+    }
+
+    #[test]
+    fn test_arena() {
+        let mut storage = [0u16; 8];
+        let outside = 0u16;
+        let arena = ProvenanceArena::from_slice_mut(&mut storage);
+        for i in 0..arena.len() {
+            let ptr = arena.ptr_of(i);
+            unsafe { *ptr = i as u16 * 3 };
+            assert_eq!(arena.index_of(ptr), Some(i));
+        }
+        assert_eq!(arena.get_ptr(8), None);
+        assert!(!arena.contains(arena.provenance().at_offset(8)));
+        assert!(!arena.contains(&outside));
+
+        // Pointers into the middle of an element aren't handles.
+        let misaligned = arena.ptr_of(1).cast::<u8>().wrapping_add(1).cast::<u16>();
+        assert_eq!(arena.index_of(misaligned), None);
+        assert_eq!(storage, [0, 3, 6, 9, 12, 15, 18, 21]);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_arena_out_of_bounds_panics() {
+        let storage = [0u8; 4];
+        let _ = ProvenanceArena::from_slice(&storage).ptr_of(4);
+    }
+}
@@ -0,0 +1,229 @@
+//! Word-sized "integer or pointer" values -- [PtrOrInt][].
+//!
+//! Interpreters, garbage collectors and persistent data structures often store values that
+//! are either a small integer or a pointer in a single word, and use the low bit (which is
+//! always 0 in an aligned pointer) to tell them apart. Storing that word as a `usize` throws
+//! away the provenance of the pointers, so [`PtrOrInt`] stores it as a pointer instead:
+//! integers are stored as [`invalid`][crate::invalid_mut] pointers, and tagging and
+//! untagging pointers is done with [`map_addr`][crate::Strict::map_addr].
+//!
+//! ```
+//! use sptr::PtrOrInt;
+//!
+//! let mut x = 10u32;
+//! let values: [PtrOrInt<u32>; 2] = [PtrOrInt::from_int(42), PtrOrInt::from_ptr(&mut x)];
+//!
+//! assert_eq!(values[0].as_int(), Some(42));
+//! let ptr = values[1].as_ptr().unwrap();
+//! unsafe { *ptr += 1 };
+//! assert_eq!(x, 11);
+//! ```
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::marker::PhantomData;
+
+/// A word that is either an integer of up to `usize::BITS - 1` bits, or a pointer to a `T`.
+///
+/// Which one it is is stored in the low bit, as determined by the [`TagConvention`] `C`.
+/// Since that bit is used for the tag, `T` must be at least 2-aligned (this is checked in
+/// debug builds when storing a pointer), and integers must be at most [`MAX_INT`].
+///
+/// This is exactly pointer-sized, and is all-zeroes for the integer 0 under [`OddPtrs`]
+/// and for the null pointer under [`OddInts`] (the default).
+#[repr(transparent)]
+pub struct PtrOrInt<T, C: TagConvention = OddInts> {
+    ptr: *mut T,
+    _marker: PhantomData<C>,
+}
+
+/// The largest integer that can be stored in a [`PtrOrInt`].
+pub const MAX_INT: usize = !0usize >> 1;
+
+/// Which value of the low bit marks a [`PtrOrInt`] as an integer.
+///
+/// Implemented by [`OddInts`] and [`OddPtrs`].
+pub trait TagConvention: private::Sealed {
+    /// The low bit of integers: the low bit of pointers is the other one.
+    const INT_TAG: usize;
+}
+
+/// Integers have their low bit set, and pointers are stored untagged (like OCaml and
+/// many Lisps). This makes unboxing pointers free.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum OddInts {}
+
+/// Pointers have their low bit set, and integers are stored shifted with it clear (like
+/// V8's "Smis"). This makes integer arithmetic on boxed values cheaper.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum OddPtrs {}
+
+impl TagConvention for OddInts {
+    const INT_TAG: usize = 1;
+}
+
+impl TagConvention for OddPtrs {
+    const INT_TAG: usize = 0;
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for super::OddInts {}
+    impl Sealed for super::OddPtrs {}
+}
+
+impl<T, C: TagConvention> PtrOrInt<T, C> {
+    const PTR_TAG: usize = C::INT_TAG ^ 1;
+
+    /// Stores an integer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `int` is greater than [`MAX_INT`].
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn from_int(int: usize) -> Self {
+        assert!(int <= MAX_INT, "{} is too big to store in a PtrOrInt", int);
+        PtrOrInt {
+            ptr: crate::invalid_mut((int << 1) | C::INT_TAG),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Stores a pointer.
+    ///
+    /// `ptr` must be at least 2-aligned, which is checked in debug builds.
+    #[inline]
+    #[must_use]
+    pub fn from_ptr(ptr: *mut T) -> Self {
+        debug_assert!(
+            ptr.addr() & 1 == 0,
+            "{:p} is not aligned enough to store in a PtrOrInt",
+            ptr
+        );
+        PtrOrInt {
+            ptr: ptr.map_addr(|a| a | Self::PTR_TAG),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this is a pointer.
+    #[inline]
+    #[must_use]
+    pub fn is_ptr(&self) -> bool {
+        self.ptr.addr() & 1 == Self::PTR_TAG
+    }
+
+    /// Returns `true` if this is an integer.
+    #[inline]
+    #[must_use]
+    pub fn is_int(&self) -> bool {
+        !self.is_ptr()
+    }
+
+    /// Gets the integer, if this is one.
+    #[inline]
+    #[must_use]
+    pub fn as_int(&self) -> Option<usize> {
+        if self.is_int() {
+            Some(self.ptr.addr() >> 1)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the pointer, with its original provenance, if this is one.
+    #[inline]
+    #[must_use]
+    pub fn as_ptr(&self) -> Option<*mut T> {
+        if self.is_ptr() {
+            Some(self.ptr.map_addr(|a| a & !1))
+        } else {
+            None
+        }
+    }
+}
+
+// Manual impls to avoid requiring `T: Clone` and friends.
+impl<T, C: TagConvention> Copy for PtrOrInt<T, C> {}
+impl<T, C: TagConvention> Clone for PtrOrInt<T, C> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, C: TagConvention> PartialEq for PtrOrInt<T, C> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+    }
+}
+impl<T, C: TagConvention> Eq for PtrOrInt<T, C> {}
+
+impl<T, C: TagConvention> core::hash::Hash for PtrOrInt<T, C> {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.ptr.hash(state);
+    }
+}
+
+impl<T, C: TagConvention> core::fmt::Debug for PtrOrInt<T, C> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (self.as_int(), self.as_ptr()) {
+            (Some(int), _) => f.debug_tuple("Int").field(&int).finish(),
+            (_, Some(ptr)) => f.debug_tuple("Ptr").field(&ptr).finish(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OddInts, OddPtrs, PtrOrInt, TagConvention, MAX_INT};
+    #[allow(unused_imports)]
+    use crate::Strict;
+
+    fn roundtrip<C: TagConvention>() {
+        let mut x = 7u64;
+        let ptr = &mut x as *mut u64;
+
+        let boxed = PtrOrInt::<u64, C>::from_ptr(ptr);
+        assert!(boxed.is_ptr());
+        assert_eq!(boxed.as_int(), None);
+        let unboxed = boxed.as_ptr().unwrap();
+        assert_eq!(unboxed, ptr);
+        unsafe { *unboxed += 1 };
+
+        for &int in &[0, 1, 12345, MAX_INT] {
+            let boxed = PtrOrInt::<u64, C>::from_int(int);
+            assert!(boxed.is_int());
+            assert_eq!(boxed.as_ptr(), None);
+            assert_eq!(boxed.as_int(), Some(int));
+        }
+        assert_eq!(x, 8);
+    }
+
+    #[test]
+    fn test_conventions() {
+        roundtrip::<OddInts>();
+        roundtrip::<OddPtrs>();
+
+        // The all-zeroes word is null or 0, depending on the convention.
+        let null = PtrOrInt::<u64, OddInts>::from_ptr(core::ptr::null_mut());
+        let zero = PtrOrInt::<u64, OddPtrs>::from_int(0);
+        assert_eq!(null.ptr.addr(), 0);
+        assert_eq!(zero.ptr.addr(), 0);
+        assert_eq!(
+            PtrOrInt::<u64, OddPtrs>::from_ptr(core::ptr::null_mut()).as_ptr(),
+            Some(core::ptr::null_mut())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "too big")]
+    fn test_int_too_big_panics() {
+        let _ = PtrOrInt::<u64>::from_int(MAX_INT + 1);
+    }
+}
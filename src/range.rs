@@ -0,0 +1,513 @@
+//! Provenance-preserving `[start, end)` pointer ranges -- [PtrRange][].
+//!
+//! Walking a buffer with a pair of raw pointers is common in parsers and other hot loops,
+//! and it tends to grow `end as usize - start as usize` math. A [`PtrRange`] does that math
+//! with [`addr`][crate::Strict::addr], and derives every pointer it hands out from the start
+//! pointer with [`wrapping_add`][] or [`with_addr`][crate::Strict::with_addr], so they all
+//! have its provenance (even the end pointer, and even if the range was built from a
+//! `Range<*const T>` whose end came from somewhere else).
+//!
+//! ```
+//! use sptr::PtrRange;
+//!
+//! let bytes = b"key=value";
+//! let range = PtrRange::from(&bytes[..]);
+//! let eq = range.iter().position(|p| unsafe { *p } == b'=').unwrap();
+//! let (key, value) = range.split_at(eq);
+//! assert_eq!(unsafe { key.as_slice() }, b"key");
+//! assert_eq!(unsafe { value.as_slice() }, b"=value");
+//! ```
+//!
+//! Zero-sized `T` are handled like `core::slice::Iter` handles them: the end address is
+//! `len` *bytes* past the start, and every element pointer is the range's start pointer
+//! (rounded down to `T`'s alignment, since the start of a split range usually isn't aligned).
+//!
+//! The tests need 1.55 (for array `map`).
+//!
+//! [`wrapping_add`]: https://doc.rust-lang.org/std/primitive.pointer.html#method.wrapping_add
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::ops::Range;
+
+/// A `[start, end)` range of pointers to `T`, which all have the start pointer's provenance.
+pub struct PtrRange<T> {
+    start: *const T,
+    end: *const T,
+}
+
+/// An iterator over the element pointers of a [`PtrRange`].
+///
+/// Created by [`PtrRange::iter`].
+pub struct Iter<T> {
+    range: PtrRange<T>,
+    /// What to yield for zero-sized `T`, since the range's start moves as we iterate.
+    zst_ptr: *const T,
+}
+
+/// An iterator over `chunk_size`-element [`PtrRange`]s of a [`PtrRange`]. The last chunk
+/// may be shorter.
+///
+/// Created by [`PtrRange::chunks`].
+pub struct Chunks<T> {
+    range: PtrRange<T>,
+    chunk_size: usize,
+}
+
+impl<T> PtrRange<T> {
+    /// Creates a range from `start` to `end`.
+    ///
+    /// `end` must be a whole number of elements past `start` (this is checked in debug
+    /// builds), but only its address is used: pointers into the range, including
+    /// [`end`][PtrRange::end], are derived from `start`.
+    #[inline]
+    #[must_use]
+    pub fn new(start: *const T, end: *const T) -> Self {
+        debug_assert!(
+            start.addr() <= end.addr(),
+            "PtrRange end is before its start"
+        );
+        // is_multiple_of requires 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        let whole = core::mem::size_of::<T>() == 0
+            || (end.addr() - start.addr()) % core::mem::size_of::<T>() == 0;
+        debug_assert!(
+            whole,
+            "PtrRange end isn't a whole number of elements after its start"
+        );
+        PtrRange {
+            start,
+            end: start.with_addr(end.addr()),
+        }
+    }
+
+    /// Creates a range of the `len` elements starting at `start`.
+    #[inline]
+    #[must_use]
+    pub fn from_start_len(start: *const T, len: usize) -> Self {
+        let end = if core::mem::size_of::<T>() == 0 {
+            start.map_addr(|a| a.wrapping_add(len))
+        } else {
+            start.wrapping_add(len)
+        };
+        PtrRange { start, end }
+    }
+
+    /// Gets the start pointer.
+    #[inline]
+    #[must_use]
+    pub fn start(&self) -> *const T {
+        self.start
+    }
+
+    /// Gets the end pointer (one past the last element), with the start pointer's provenance.
+    #[inline]
+    #[must_use]
+    pub fn end(&self) -> *const T {
+        self.end
+    }
+
+    /// Gets the number of elements in the range.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let bytes = self.end.addr() - self.start.addr();
+        match core::mem::size_of::<T>() {
+            0 => bytes,
+            size => bytes / size,
+        }
+    }
+
+    /// Returns `true` if the range has no elements.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.start.addr() == self.end.addr()
+    }
+
+    /// Returns `true` if `ptr` points into the range.
+    ///
+    /// `ptr` doesn't have to point to the start of an element. For zero-sized `T`, this is
+    /// only `true` for the pointer that every element of the range is at.
+    #[inline]
+    #[must_use]
+    pub fn contains(&self, ptr: *const T) -> bool {
+        if core::mem::size_of::<T>() == 0 {
+            return !self.is_empty() && ptr.addr() == self.zst_ptr().addr();
+        }
+        self.start.addr() <= ptr.addr() && ptr.addr() < self.end.addr()
+    }
+
+    /// Gets a pointer to the element at `index`, with the start pointer's provenance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn at(&self, index: usize) -> *const T {
+        assert!(
+            index < self.len(),
+            "index {} is out of bounds of a PtrRange of length {}",
+            index,
+            self.len()
+        );
+        self.element(index)
+    }
+
+    /// Splits the range into `[start, mid)` and `[mid, end)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mid > len`.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn split_at(&self, mid: usize) -> (Self, Self) {
+        assert!(
+            mid <= self.len(),
+            "mid {} is out of bounds of a PtrRange of length {}",
+            mid,
+            self.len()
+        );
+        let middle = Self::from_start_len(self.start, mid).end;
+        (
+            PtrRange {
+                start: self.start,
+                end: middle,
+            },
+            PtrRange {
+                start: middle,
+                end: self.end,
+            },
+        )
+    }
+
+    /// Iterates over pointers to each element of the range.
+    #[inline]
+    #[must_use]
+    pub fn iter(&self) -> Iter<T> {
+        Iter {
+            range: *self,
+            zst_ptr: self.zst_ptr(),
+        }
+    }
+
+    /// Iterates over `chunk_size`-element subranges of the range. The last one may be shorter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is 0.
+    #[inline]
+    #[must_use]
+    #[track_caller]
+    pub fn chunks(&self, chunk_size: usize) -> Chunks<T> {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+        Chunks {
+            range: *self,
+            chunk_size,
+        }
+    }
+
+    /// Converts the range to a `Range<*const T>`.
+    #[inline]
+    #[must_use]
+    pub fn as_range(&self) -> Range<*const T> {
+        self.start..self.end
+    }
+
+    /// Gets the range as a slice.
+    ///
+    /// # Safety
+    ///
+    /// The range must satisfy all the requirements of [`core::slice::from_raw_parts`]
+    /// for the lifetime `'a`.
+    #[inline]
+    #[must_use]
+    pub unsafe fn as_slice<'a>(&self) -> &'a [T] {
+        core::slice::from_raw_parts(self.slice_start(), self.len())
+    }
+
+    /// Gets the range as a mutable slice.
+    ///
+    /// # Safety
+    ///
+    /// The range must satisfy all the requirements of [`core::slice::from_raw_parts_mut`]
+    /// for the lifetime `'a`, including having been created from a pointer that allows
+    /// writes.
+    #[inline]
+    #[must_use]
+    pub unsafe fn as_mut_slice<'a>(&self) -> &'a mut [T] {
+        core::slice::from_raw_parts_mut(self.slice_start() as *mut T, self.len())
+    }
+
+    /// The data pointer for `as_slice`, which must be aligned even for ZSTs.
+    fn slice_start(&self) -> *const T {
+        if core::mem::size_of::<T>() == 0 {
+            core::ptr::NonNull::dangling().as_ptr()
+        } else {
+            self.start
+        }
+    }
+
+    /// The pointer to every element of a ZST range: the start, aligned.
+    fn zst_ptr(&self) -> *const T {
+        let align = core::mem::align_of::<T>();
+        self.start.map_addr(|a| a & !(align - 1))
+    }
+
+    fn element(&self, index: usize) -> *const T {
+        if core::mem::size_of::<T>() == 0 {
+            self.zst_ptr()
+        } else {
+            self.start.wrapping_add(index)
+        }
+    }
+}
+
+impl<'a, T> From<&'a [T]> for PtrRange<T> {
+    #[inline]
+    fn from(slice: &'a [T]) -> Self {
+        PtrRange::from_start_len(slice.as_ptr(), slice.len())
+    }
+}
+
+impl<'a, T> From<&'a mut [T]> for PtrRange<T> {
+    #[inline]
+    fn from(slice: &'a mut [T]) -> Self {
+        PtrRange::from_start_len(slice.as_mut_ptr(), slice.len())
+    }
+}
+
+impl<T> From<Range<*const T>> for PtrRange<T> {
+    #[inline]
+    fn from(range: Range<*const T>) -> Self {
+        PtrRange::new(range.start, range.end)
+    }
+}
+
+impl<T> From<PtrRange<T>> for Range<*const T> {
+    #[inline]
+    fn from(range: PtrRange<T>) -> Self {
+        range.as_range()
+    }
+}
+
+impl<T> IntoIterator for PtrRange<T> {
+    type Item = *const T;
+    type IntoIter = Iter<T>;
+
+    #[inline]
+    fn into_iter(self) -> Iter<T> {
+        self.iter()
+    }
+}
+
+impl<T> Iterator for Iter<T> {
+    type Item = *const T;
+
+    #[inline]
+    fn next(&mut self) -> Option<*const T> {
+        if self.range.is_empty() {
+            return None;
+        }
+        let (first, rest) = self.range.split_at(1);
+        self.range = rest;
+        Some(self.yielded(first.start))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.range.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<*const T> {
+        if self.range.is_empty() {
+            return None;
+        }
+        let (rest, last) = self.range.split_at(self.range.len() - 1);
+        self.range = rest;
+        Some(self.yielded(last.start))
+    }
+}
+
+impl<T> Iter<T> {
+    fn yielded(&self, ptr: *const T) -> *const T {
+        if core::mem::size_of::<T>() == 0 {
+            self.zst_ptr
+        } else {
+            ptr
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<T> {}
+impl<T> core::iter::FusedIterator for Iter<T> {}
+
+impl<T> Iterator for Chunks<T> {
+    type Item = PtrRange<T>;
+
+    #[inline]
+    fn next(&mut self) -> Option<PtrRange<T>> {
+        if self.range.is_empty() {
+            return None;
+        }
+        let mid = self.chunk_size.min(self.range.len());
+        let (chunk, rest) = self.range.split_at(mid);
+        self.range = rest;
+        Some(chunk)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.range.len();
+        // is_multiple_of requires 1.87
+        #[allow(clippy::manual_is_multiple_of)]
+        let chunks = len / self.chunk_size + (len % self.chunk_size != 0) as usize;
+        (chunks, Some(chunks))
+    }
+}
+
+impl<T> ExactSizeIterator for Chunks<T> {}
+impl<T> core::iter::FusedIterator for Chunks<T> {}
+
+// Manual impls to avoid requiring `T: Clone` and friends.
+impl<T> Copy for PtrRange<T> {}
+impl<T> Clone for PtrRange<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for PtrRange<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.end == other.end
+    }
+}
+impl<T> Eq for PtrRange<T> {}
+
+impl<T> core::fmt::Debug for PtrRange<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PtrRange")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+impl<T> Clone for Iter<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Iter {
+            range: self.range,
+            zst_ptr: self.zst_ptr,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for Iter<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Iter").field(&self.range).finish()
+    }
+}
+
+impl<T> Clone for Chunks<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Chunks {
+            range: self.range,
+            chunk_size: self.chunk_size,
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for Chunks<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Chunks")
+            .field("range", &self.range)
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PtrRange;
+    #[allow(unused_imports)]
+    use crate::Strict;
+
+    #[test]
+    fn test_range() {
+        let arr = [1u32, 2, 3, 4, 5];
+        let range = PtrRange::from(&arr[..]);
+        assert_eq!(range.len(), 5);
+        assert_eq!(range.end().addr() - range.start().addr(), 20);
+        assert!(range.contains(&arr[4]));
+        assert!(!range.contains(range.end()));
+        assert_eq!(unsafe { *range.at(3) }, 4);
+
+        let (front, back) = range.split_at(2);
+        assert_eq!(unsafe { front.as_slice() }, [1, 2]);
+        assert_eq!(unsafe { back.as_slice() }, [3, 4, 5]);
+        assert_eq!(front.end(), back.start());
+
+        let via_range: core::ops::Range<*const u32> = range.into();
+        assert_eq!(PtrRange::from(via_range), range);
+    }
+
+    #[test]
+    fn test_iter() {
+        let arr = [1u8, 2, 3, 4, 5];
+        let range = PtrRange::from(&arr[..]);
+        let mut iter = range.iter();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next().map(|p| unsafe { *p }), Some(1));
+        assert_eq!(iter.next_back().map(|p| unsafe { *p }), Some(5));
+        let rest: [u8; 3] = [0, 1, 2].map(|_| unsafe { *iter.next().unwrap() });
+        assert_eq!(rest, [2, 3, 4]);
+        assert!(iter.next().is_none());
+
+        let chunks = range.chunks(2);
+        assert_eq!(chunks.len(), 3);
+        let lens: [usize; 3] = {
+            let mut chunks = chunks;
+            [0; 3].map(|_| chunks.next().unwrap().len())
+        };
+        assert_eq!(lens, [2, 2, 1]);
+    }
+
+    #[test]
+    fn test_zst() {
+        let arr = [(), (), ()];
+        let range = PtrRange::from(&arr[..]);
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.iter().count(), 3);
+        assert!(range.iter().all(|p| p == range.start()));
+        assert_eq!(range.split_at(1).1.len(), 2);
+        assert_eq!(unsafe { range.as_slice() }.len(), 3);
+
+        // Splitting moves the start by a byte, which needs realigning for aligned ZSTs.
+        let arr = [[0u32; 0]; 3];
+        let (_, back) = PtrRange::from(&arr[..]).split_at(1);
+        assert_eq!(back.len(), 2);
+        assert_eq!(unsafe { back.as_slice() }.len(), 2);
+        assert!(back.iter().all(|p| p.addr() % 4 == 0));
+        assert_eq!(back.at(1).addr() % 4, 0);
+        assert!(back.contains(back.at(0)));
+        assert!(back.iter().all(|p| back.contains(p)));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_split_out_of_bounds_panics() {
+        let arr = [0u8; 2];
+        let _ = PtrRange::from(&arr[..]).split_at(3);
+    }
+}
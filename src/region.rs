@@ -0,0 +1,87 @@
+//! The base pointer and length shared by [`Region`][crate::Region] and
+//! [`Swizzler`][crate::swizzle::Swizzler].
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::marker::PhantomData;
+
+/// `len` bytes starting at `base`, and the provenance of `base` to access them with.
+///
+/// The lifetime `'a` ties this to the borrow of the underlying memory when it's created
+/// from a slice.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct RawRegion<'a> {
+    base: *mut u8,
+    len: usize,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> RawRegion<'a> {
+    #[inline]
+    pub(crate) fn from_slice_mut<T>(slice: &'a mut [T]) -> Self {
+        RawRegion {
+            len: core::mem::size_of_val(slice),
+            base: slice.as_mut_ptr() as *mut u8,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn from_slice<T>(slice: &'a [T]) -> Self {
+        RawRegion {
+            len: core::mem::size_of_val(slice),
+            base: slice.as_ptr() as *mut u8,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn from_raw_parts(base: *mut u8, len: usize) -> Self {
+        RawRegion {
+            base,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(not(feature = "compressed"), allow(dead_code))]
+    pub(crate) fn base(&self) -> *mut u8 {
+        self.base
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if a whole `T` at `offset` bytes from the base is inside the region.
+    #[inline]
+    pub(crate) fn fits<T>(&self, offset: usize) -> bool {
+        offset <= self.len && core::mem::size_of::<T>() <= self.len - offset
+    }
+
+    /// Gets the offset of `ptr` from the base, if the whole `T` it points to is inside the
+    /// region.
+    #[inline]
+    pub(crate) fn offset_of<T>(&self, ptr: *const T) -> Option<usize> {
+        let offset = ptr.addr().checked_sub(self.base.addr())?;
+        if self.fits::<T>(offset) {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Gets a pointer `offset` bytes from the base, with the provenance of the base.
+    #[inline]
+    pub(crate) fn at<T>(&self, offset: usize) -> *mut T {
+        self.base.with_addr(self.base.addr().wrapping_add(offset)) as *mut T
+    }
+
+    /// Gets a null pointer, with the provenance of the base.
+    #[inline]
+    pub(crate) fn null<T>(&self) -> *mut T {
+        self.base.with_addr(0) as *mut T
+    }
+}
@@ -0,0 +1,321 @@
+//! Self-relative pointers -- [RelPtr][].
+//!
+//! Requires 1.42 (for `core::ptr::slice_from_raw_parts`), and 1.51 to run the tests (for
+//! `core::ptr::addr_of!`).
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::marker::PhantomData;
+
+/// A pointer that stores the *offset* from its own address to its target.
+///
+/// If you relocate the whole region that contains both a `RelPtr` and its target (by
+/// memory-mapping a file, `memcpy`ing a buffer, reallocating a `Vec`, ...), the `RelPtr` still
+/// points at the same place *relative to the region*, which is exactly what you want for
+/// on-disk and shared-memory data structures.
+///
+/// Resolving a `RelPtr` is done with [`with_addr`][Strict::with_addr] on a pointer to the
+/// `RelPtr` itself, so the result has the provenance of whatever pointer you used to reach the
+/// `RelPtr`. This means that *how* you access the `RelPtr` matters:
+///
+/// * [`resolve`][RelPtr::resolve]/[`resolve_mut`][RelPtr::resolve_mut] take a raw pointer to
+///   the `RelPtr`. If that pointer was derived from a pointer to the whole buffer, the result
+///   can access the whole buffer. This is the strictly correct way to use this type.
+/// * [`as_ptr`][RelPtr::as_ptr]/[`as_mut_ptr`][RelPtr::as_mut_ptr] take `&self`, which is
+///   convenient, but a reference only has provenance over the `RelPtr`'s own bytes under
+///   aliasing models like Stacked Borrows, so Miri may reject accesses through the result.
+///
+/// An offset of 0 is used to represent null, so a `RelPtr` can't point to itself.
+///
+/// `Offset` can be any of `i8`, `i16`, `i32`, `i64`, or `isize`, trading space for reach.
+/// Slices and `str` are supported as targets by storing their length alongside the offset.
+/// Trait objects are not supported, because their vtable can't be meaningfully relocated.
+#[repr(C)]
+pub struct RelPtr<T: ?Sized + RelPointee, Offset: self::Offset = i32> {
+    offset: Offset,
+    meta: T::Metadata,
+    _marker: PhantomData<*const T>,
+}
+
+/// The types that can be used as the offset of a [`RelPtr`].
+///
+/// This trait is sealed and implemented for `i8`, `i16`, `i32`, `i64`, and `isize`.
+pub trait Offset: Copy + Eq + core::fmt::Debug + private::Sealed {
+    /// The offset that represents null.
+    const NULL: Self;
+
+    /// Converts the offset into an `isize`, for pointer arithmetic.
+    fn to_isize(self) -> isize;
+
+    /// Converts an `isize` into this offset type, failing if it doesn't fit.
+    fn from_isize(offset: isize) -> Option<Self>;
+}
+
+macro_rules! offset_impls {
+    ($($int_ty: ident),*) => {
+        $(
+            impl private::Sealed for $int_ty {}
+            impl Offset for $int_ty {
+                const NULL: Self = 0;
+
+                #[inline]
+                fn to_isize(self) -> isize {
+                    self as isize
+                }
+
+                #[inline]
+                fn from_isize(offset: isize) -> Option<Self> {
+                    use core::convert::TryFrom;
+                    <$int_ty>::try_from(offset).ok()
+                }
+            }
+        )*
+    };
+}
+
+offset_impls!(i8, i16, i32, i64, isize);
+
+/// The types a [`RelPtr`] can point to.
+///
+/// This trait is sealed and implemented for all `Sized` types, and for slices and `str`
+/// (which store their length alongside the offset).
+pub trait RelPointee: crate::private::RawParts {}
+
+impl<T: ?Sized + crate::private::RawParts> RelPointee for T {}
+
+/// The error returned by [`RelPtr::try_set`] when the target can't be represented.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OffsetOverflow;
+
+impl core::fmt::Display for OffsetOverflow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "target is out of range of the RelPtr's offset type")
+    }
+}
+
+impl<T: ?Sized + RelPointee, O: Offset> RelPtr<T, O> {
+    /// Creates a null `RelPtr`.
+    #[inline]
+    #[must_use]
+    pub fn null() -> Self {
+        RelPtr {
+            offset: O::NULL,
+            meta: T::Metadata::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns `true` if this `RelPtr` is null.
+    #[inline]
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        self.offset == O::NULL
+    }
+
+    /// Points this `RelPtr` at `target`, or makes it null if `target` is null.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the distance to `target` doesn't fit in `Offset` (see
+    /// [`try_set`][RelPtr::try_set] for a non-panicking version).
+    #[inline]
+    pub fn set(&mut self, target: *const T) {
+        self.try_set(target).expect("RelPtr target out of range")
+    }
+
+    /// Points this `RelPtr` at `target`, or makes it null if `target` is null.
+    ///
+    /// Fails if the distance to `target` doesn't fit in `Offset`, or if `target` is the
+    /// `RelPtr` itself (which can't be distinguished from null). On failure, `self` is
+    /// left unchanged.
+    pub fn try_set(&mut self, target: *const T) -> Result<(), OffsetOverflow> {
+        let target_addr = (target as *const u8).addr();
+        if target_addr == 0 {
+            *self = Self::null();
+            return Ok(());
+        }
+
+        let self_addr = (self as *const Self as *const u8).addr();
+        let offset = (target_addr as isize).wrapping_sub(self_addr as isize);
+        // Check the subtraction didn't wrap around the address space.
+        if (offset > 0) != (target_addr > self_addr) {
+            return Err(OffsetOverflow);
+        }
+        match O::from_isize(offset) {
+            Some(offset) if offset != O::NULL => {
+                self.offset = offset;
+                self.meta = T::metadata(target);
+                Ok(())
+            }
+            _ => Err(OffsetOverflow),
+        }
+    }
+
+    /// Makes this `RelPtr` null.
+    #[inline]
+    pub fn set_null(&mut self) {
+        *self = Self::null();
+    }
+
+    /// Gets the raw offset from this `RelPtr` to its target (`0` for null).
+    #[inline]
+    #[must_use]
+    pub fn offset(&self) -> O {
+        self.offset
+    }
+
+    /// Resolves the `RelPtr` that `this` points to, with the provenance of `this`.
+    ///
+    /// Returns a null pointer if the `RelPtr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be valid for reads.
+    #[inline]
+    #[must_use]
+    pub unsafe fn resolve(this: *const Self) -> *const T {
+        Self::resolve_mut(this as *mut Self)
+    }
+
+    /// Resolves the `RelPtr` that `this` points to, with the provenance of `this`.
+    ///
+    /// Returns a null pointer if the `RelPtr` is null.
+    ///
+    /// # Safety
+    ///
+    /// `this` must be valid for reads.
+    #[must_use]
+    pub unsafe fn resolve_mut(this: *mut Self) -> *mut T {
+        let offset = (*this).offset;
+        let meta = (*this).meta;
+        let base = this as *mut u8;
+        let data = if offset == O::NULL {
+            base.with_addr(0)
+        } else {
+            base.map_addr(|a| a.wrapping_add(offset.to_isize() as usize))
+        };
+        T::from_raw_parts(data as *const (), meta) as *mut T
+    }
+
+    /// Gets a pointer to the target, with the provenance of `&self`.
+    ///
+    /// See the [type-level docs][RelPtr] for why you might want
+    /// [`resolve`][RelPtr::resolve] instead.
+    #[inline]
+    #[must_use]
+    pub fn as_ptr(&self) -> *const T {
+        // SAFETY: `self` is a reference, and so valid for reads.
+        unsafe { Self::resolve(self) }
+    }
+
+    /// Gets a pointer to the target, with the provenance of `&mut self`.
+    ///
+    /// See the [type-level docs][RelPtr] for why you might want
+    /// [`resolve_mut`][RelPtr::resolve_mut] instead.
+    #[inline]
+    #[must_use]
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        // SAFETY: `self` is a reference, and so valid for reads.
+        unsafe { Self::resolve_mut(self) }
+    }
+}
+
+impl<T: ?Sized + RelPointee, O: Offset> Default for RelPtr<T, O> {
+    #[inline]
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T: ?Sized + RelPointee, O: Offset> core::fmt::Debug for RelPtr<T, O> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RelPtr")
+            .field("offset", &self.offset)
+            .field("meta", &self.meta)
+            .finish()
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OffsetOverflow, RelPtr};
+
+    #[repr(C)]
+    struct Node {
+        next: RelPtr<u32>,
+        slice: RelPtr<[u32], i16>,
+        string: RelPtr<str, i8>,
+        data: [u32; 4],
+        text: [u8; 5],
+    }
+
+    #[test]
+    fn test_rel_ptr_relocate() {
+        let mut nodes = [
+            Node {
+                next: RelPtr::null(),
+                slice: RelPtr::null(),
+                string: RelPtr::null(),
+                data: [1, 2, 3, 4],
+                text: *b"hello",
+            },
+            Node {
+                next: RelPtr::null(),
+                slice: RelPtr::null(),
+                string: RelPtr::null(),
+                data: [0; 4],
+                text: [0; 5],
+            },
+        ];
+        assert!(nodes[0].next.is_null());
+        assert!(nodes[0].next.as_ptr().is_null());
+
+        let nodes_ptr = nodes.as_mut_ptr();
+        unsafe {
+            let node = &mut *nodes_ptr;
+            node.next.set(&node.data[2]);
+            node.slice.set(&node.data[1..3]);
+            node.string
+                .set(core::str::from_utf8(&node.text[1..4]).unwrap());
+
+            // Relocate the first node into the second slot.
+            core::ptr::copy_nonoverlapping(nodes_ptr, nodes_ptr.add(1), 1);
+
+            let moved = nodes_ptr.add(1);
+            let next = RelPtr::resolve(core::ptr::addr_of!((*moved).next));
+            assert_eq!(next, &(*moved).data[2] as *const u32);
+            assert_eq!(*next, 3);
+
+            let slice = &*RelPtr::resolve(core::ptr::addr_of!((*moved).slice));
+            assert_eq!(slice, &[2, 3]);
+            assert_eq!(slice.as_ptr(), &(*moved).data[1] as *const u32);
+
+            let string = &*RelPtr::resolve(core::ptr::addr_of!((*moved).string));
+            assert_eq!(string, "ell");
+        }
+
+        nodes[1].next.set_null();
+        assert!(nodes[1].next.is_null());
+        assert!(nodes[1].next.as_ptr().is_null());
+    }
+
+    #[test]
+    fn test_rel_ptr_overflow() {
+        let mut ptr = RelPtr::<u8, i8>::null();
+        let far = [0u8; 256];
+
+        assert_eq!(ptr.try_set(&far[255]), Err(OffsetOverflow));
+        assert!(ptr.is_null());
+
+        let own_addr = &ptr as *const RelPtr<u8, i8> as *const u8;
+        assert_eq!(ptr.try_set(own_addr), Err(OffsetOverflow));
+
+        let mut wide = RelPtr::<u8, isize>::null();
+        wide.set(&far[255]);
+        assert_eq!(wide.as_ptr(), &far[255] as *const u8);
+    }
+}
@@ -0,0 +1,224 @@
+//! Pointer swizzling for snapshotting pointer graphs -- [Swizzler][].
+//!
+//! "Swizzling" is the act of converting the pointers inside a data structure into something
+//! position-independent (here, offsets into a buffer) so it can be written out, and
+//! "unswizzling" is converting them back into real pointers after it's read back in, possibly
+//! at a completely different address.
+//!
+//! The naive way to do this is with `ptr as usize - base as usize` and
+//! `(base as usize + offset) as *mut T`, but the second cast produces a pointer with no
+//! (or an unknown) provenance. [`Swizzler::unswizzle`] instead uses
+//! [`with_addr`][crate::Strict::with_addr] on the *new* buffer's pointer, so restored
+//! pointers are properly derived from the memory they point into.
+//!
+//! ```
+//! use sptr::swizzle::Swizzler;
+//!
+//! let old = [1u32, 2, 3, 4];
+//! let token = Swizzler::from_slice(&old).swizzle(&old[2]).unwrap();
+//!
+//! let mut new = [0u32; 4];
+//! new.copy_from_slice(&old);
+//! let restored = Swizzler::from_slice_mut(&mut new).unswizzle::<u32>(token).unwrap();
+//! assert_eq!(unsafe { *restored }, 3);
+//! ```
+
+use crate::region::RawRegion;
+
+/// Converts pointers into a buffer to position-independent `u64` tokens, and back.
+///
+/// A token is the pointer's offset from the start of the buffer plus one, so that
+/// the token `0` can represent null (and zeroed memory unswizzles to null pointers).
+/// Tokens are always `u64`, even on 32-bit platforms, so that snapshots are portable.
+#[derive(Copy, Clone, Debug)]
+pub struct Swizzler<'a> {
+    raw: RawRegion<'a>,
+}
+
+/// The error returned when a pointer or token doesn't refer to something inside
+/// the buffer of a [`Swizzler`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OutOfRange;
+
+impl core::fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "pointer is out of range of the swizzled buffer")
+    }
+}
+
+/// The token that null pointers swizzle to.
+pub const NULL_TOKEN: u64 = 0;
+
+impl<'a> Swizzler<'a> {
+    /// Creates a `Swizzler` for the given buffer.
+    #[inline]
+    #[must_use]
+    pub fn from_slice_mut<T>(slice: &'a mut [T]) -> Self {
+        Swizzler {
+            raw: RawRegion::from_slice_mut(slice),
+        }
+    }
+
+    /// Creates a `Swizzler` for the given buffer.
+    ///
+    /// Pointers unswizzled by this `Swizzler` must not be written through.
+    #[inline]
+    #[must_use]
+    pub fn from_slice<T>(slice: &'a [T]) -> Self {
+        Swizzler {
+            raw: RawRegion::from_slice(slice),
+        }
+    }
+
+    /// Creates a `Swizzler` for the `len` bytes starting at `base`.
+    ///
+    /// This is safe, like `Provenance::from_raw_parts`: the `Swizzler` only makes raw
+    /// pointers, and it's on you to only dereference the ones that `base`'s provenance
+    /// actually allows.
+    #[inline]
+    #[must_use]
+    pub fn from_raw_parts(base: *mut u8, len: usize) -> Self {
+        Swizzler {
+            raw: RawRegion::from_raw_parts(base, len),
+        }
+    }
+
+    /// Converts `ptr` into a token.
+    ///
+    /// A null `ptr` becomes [`NULL_TOKEN`]. Fails if the `T` that `ptr` points to isn't
+    /// entirely inside the buffer.
+    pub fn swizzle<T>(&self, ptr: *const T) -> Result<u64, OutOfRange> {
+        if ptr.is_null() {
+            return Ok(NULL_TOKEN);
+        }
+        let offset = self.raw.offset_of(ptr).ok_or(OutOfRange)?;
+        Ok(offset as u64 + 1)
+    }
+
+    /// Converts a token back into a pointer, with the provenance of this `Swizzler`'s buffer.
+    ///
+    /// [`NULL_TOKEN`] becomes a null pointer. Fails if the `T` that the token refers to
+    /// isn't entirely inside the buffer.
+    pub fn unswizzle<T>(&self, token: u64) -> Result<*mut T, OutOfRange> {
+        if token == NULL_TOKEN {
+            return Ok(self.raw.null());
+        }
+        // Tokens can come from anywhere, so don't trust them to fit in a usize.
+        let offset = token - 1;
+        if offset > self.raw.len() as u64 || !self.raw.fits::<T>(offset as usize) {
+            return Err(OutOfRange);
+        }
+        Ok(self.raw.at(offset as usize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OutOfRange, Swizzler, NULL_TOKEN};
+
+    #[derive(Copy, Clone)]
+    #[repr(C)]
+    struct Node {
+        value: u32,
+        next: *mut Node,
+        other: *mut u32,
+    }
+
+    const NODE_BYTES: usize = 4 + 8 + 8;
+    const NODES: usize = 4;
+
+    fn empty() -> [Node; NODES] {
+        [Node {
+            value: 0,
+            next: core::ptr::null_mut(),
+            other: core::ptr::null_mut(),
+        }; NODES]
+    }
+
+    /// Serialize the nodes as little-endian `value`, `next`, `other`.
+    fn snapshot(nodes: &[Node; NODES]) -> [u8; NODE_BYTES * NODES] {
+        let swizzler = Swizzler::from_slice(nodes);
+        let mut bytes = [0u8; NODE_BYTES * NODES];
+        for (node, out) in nodes.iter().zip(bytes.chunks_mut(NODE_BYTES)) {
+            out[..4].copy_from_slice(&node.value.to_le_bytes());
+            out[4..12].copy_from_slice(&swizzler.swizzle(node.next).unwrap().to_le_bytes());
+            out[12..].copy_from_slice(&swizzler.swizzle(node.other).unwrap().to_le_bytes());
+        }
+        bytes
+    }
+
+    fn restore(bytes: &[u8; NODE_BYTES * NODES], nodes: &mut [Node; NODES]) {
+        let u64_at = |i: usize| {
+            let mut buf = [0; 8];
+            buf.copy_from_slice(&bytes[i..i + 8]);
+            u64::from_le_bytes(buf)
+        };
+        let swizzler = Swizzler::from_slice_mut(nodes);
+        let base = swizzler.unswizzle::<Node>(1).unwrap();
+        for i in 0..NODES {
+            let at = i * NODE_BYTES;
+            let mut value = [0; 4];
+            value.copy_from_slice(&bytes[at..at + 4]);
+            let node = Node {
+                value: u32::from_le_bytes(value),
+                next: swizzler.unswizzle(u64_at(at + 4)).unwrap(),
+                other: swizzler.unswizzle(u64_at(at + 12)).unwrap(),
+            };
+            unsafe { base.add(i).write(node) };
+        }
+    }
+
+    #[test]
+    fn test_swizzle_roundtrip() {
+        let mut old = empty();
+        let old_ptr = old.as_mut_ptr();
+        unsafe {
+            for i in 0..NODES {
+                (*old_ptr.add(i)).value = 10 + i as u32;
+                (*old_ptr.add(i)).next = old_ptr.add((i + 1) % NODES);
+            }
+            (*old_ptr.add(2)).other = &mut (*old_ptr.add(1)).value;
+        }
+        let bytes = snapshot(&old);
+
+        let mut new = empty();
+        restore(&bytes, &mut new);
+
+        // The graph has the same shape, but points into the new buffer.
+        let new_ptr = new.as_mut_ptr();
+        unsafe {
+            let mut cur = new_ptr;
+            for i in 0..NODES {
+                assert_eq!((*cur).value, 10 + i as u32);
+                cur = (*cur).next;
+            }
+            assert_eq!(cur, new_ptr);
+
+            *(*new_ptr.add(2)).other += 100;
+            assert_eq!((*new_ptr.add(1)).value, 111);
+            (*new_ptr.add(1)).value = 11;
+        }
+        assert!(new[0].other.is_null());
+
+        // And re-snapshotting it produces exactly the same bytes.
+        assert_eq!(&snapshot(&new)[..], &bytes[..]);
+    }
+
+    #[test]
+    fn test_swizzle_out_of_range() {
+        let mut buf = [0u32; 4];
+        let outside = 0u32;
+        let swizzler = Swizzler::from_slice_mut(&mut buf);
+
+        assert_eq!(swizzler.swizzle(&outside), Err(OutOfRange));
+        assert_eq!(swizzler.swizzle(core::ptr::null::<u32>()), Ok(NULL_TOKEN));
+        assert_eq!(swizzler.swizzle(swizzler.raw.base() as *const u32), Ok(1));
+
+        // Tokens for the last element, one past the end, and way past the end.
+        assert!(swizzler.unswizzle::<u32>(13).is_ok());
+        assert_eq!(swizzler.unswizzle::<u32>(14), Err(OutOfRange));
+        assert_eq!(swizzler.unswizzle::<u32>(17), Err(OutOfRange));
+        assert_eq!(swizzler.unswizzle::<u32>(u64::MAX), Err(OutOfRange));
+        assert_eq!(swizzler.unswizzle::<[u32; 5]>(1), Err(OutOfRange));
+    }
+}
@@ -0,0 +1,252 @@
+//! XORed pointers -- [XorPtr][] and [MangledPtr][].
+//!
+//! There are two very different reasons to XOR a pointer:
+//!
+//! * **XOR linked lists** store `prev ^ next` in each node, and recover one neighbour by
+//!   XORing in the address of the other. The result depends on the addresses of *two*
+//!   pointers, so there is no single pointer it can inherit provenance from, and this
+//!   fundamentally requires [`expose_addr`][crate::Strict::expose_addr] and
+//!   [`from_exposed_addr`][crate::from_exposed_addr]. [`XorPtr`] wraps that up so it
+//!   at least only happens in one place.
+//!
+//! * **Pointer mangling** (like glibc's `PTR_MANGLE`) XORs a pointer with a secret, so that
+//!   an attacker who can overwrite it can't aim it anywhere useful. That's just
+//!   a transformation of one pointer's address, so [`MangledPtr`] does it with
+//!   [`map_addr`][crate::Strict::map_addr] and keeps the provenance.
+
+#[allow(unused_imports)]
+use crate::Strict;
+use core::marker::PhantomData;
+
+/// The link of an XOR linked list node: the XOR of the addresses of its two neighbours.
+///
+/// **This exposes the provenance of both neighbours**, and the pointers it gives back come
+/// from [`from_exposed_addr_mut`][crate::from_exposed_addr_mut], so it is not compatible
+/// with Strict Provenance (or CHERI). There is no way around that for XOR linked lists, but
+/// all the exposing happens in this type, and the pointers you pass in otherwise keep
+/// their provenance.
+///
+/// ```
+/// use sptr::XorPtr;
+///
+/// struct Node {
+///     value: u32,
+///     link: XorPtr<Node>,
+/// }
+///
+/// let mut nodes = [10, 20, 30].map(|value| Node { value, link: XorPtr::default() });
+/// let ptrs = [0, 1, 2].map(|i| &mut nodes[i] as *mut Node);
+/// let null = core::ptr::null_mut();
+/// unsafe {
+///     (*ptrs[0]).link = XorPtr::new(null, ptrs[1]);
+///     (*ptrs[1]).link = XorPtr::new(ptrs[0], ptrs[2]);
+///     (*ptrs[2]).link = XorPtr::new(ptrs[1], null);
+///
+///     // Walk the list forwards...
+///     let (mut prev, mut cur) = (null, ptrs[0]);
+///     let mut sum = 0;
+///     while !cur.is_null() {
+///         sum += (*cur).value;
+///         let next = (*cur).link.other(prev);
+///         prev = cur;
+///         cur = next;
+///     }
+///     assert_eq!(sum, 60);
+/// }
+/// ```
+pub struct XorPtr<T> {
+    link: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> XorPtr<T> {
+    /// Creates the link between two neighbours, either (or both) of which may be null.
+    ///
+    /// This exposes both pointers.
+    #[inline]
+    #[must_use]
+    #[allow(deprecated)]
+    pub fn new(a: *mut T, b: *mut T) -> Self {
+        XorPtr {
+            link: a.expose_addr() ^ b.expose_addr(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Given one neighbour, gets the other.
+    ///
+    /// The result is only valid to use if `known` really is one of the neighbours this link
+    /// was created with, and is null if the other neighbour was.
+    #[inline]
+    #[must_use]
+    #[allow(deprecated)]
+    pub fn other(&self, known: *const T) -> *mut T {
+        crate::from_exposed_addr_mut(self.link ^ known.addr())
+    }
+
+    /// Gets the XORed addresses.
+    #[inline]
+    #[must_use]
+    pub fn link(&self) -> usize {
+        self.link
+    }
+}
+
+/// The link between two null neighbours.
+impl<T> Default for XorPtr<T> {
+    #[inline]
+    fn default() -> Self {
+        XorPtr {
+            link: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// A pointer whose address is XORed with a secret key, like glibc's `PTR_MANGLE`.
+///
+/// The key isn't stored, so it has to be passed back in to get the pointer out. Unlike
+/// [`XorPtr`], this only ever changes the pointer's address with
+/// [`map_addr`][crate::Strict::map_addr], so the pointer keeps its provenance and this is
+/// fully compatible with Strict Provenance. The mangled pointer will usually point
+/// somewhere nonsensical, but that's fine, since it's never dereferenced.
+///
+/// The key should have its low bits clear if you want the mangled pointer to keep its
+/// alignment (not that anything requires that).
+///
+/// ```
+/// use sptr::MangledPtr;
+///
+/// const KEY: usize = 0x5a5a_5a50;
+///
+/// let mut x = 5;
+/// let mangled = MangledPtr::new(&mut x, KEY);
+/// unsafe { *mangled.unmangle(KEY) += 1 };
+/// assert_eq!(x, 6);
+/// ```
+pub struct MangledPtr<T> {
+    ptr: *mut T,
+}
+
+impl<T> MangledPtr<T> {
+    /// Mangles `ptr` with `key`.
+    #[inline]
+    #[must_use]
+    pub fn new(ptr: *mut T, key: usize) -> Self {
+        MangledPtr {
+            ptr: ptr.map_addr(|a| a ^ key),
+        }
+    }
+
+    /// Gets the original pointer back, with its provenance.
+    ///
+    /// If `key` isn't the key this was mangled with, the result will have the right
+    /// provenance and a garbage address.
+    #[inline]
+    #[must_use]
+    pub fn unmangle(&self, key: usize) -> *mut T {
+        self.ptr.map_addr(|a| a ^ key)
+    }
+
+    /// Re-mangles the pointer with a new key.
+    #[inline]
+    #[must_use]
+    pub fn rekey(&self, old_key: usize, new_key: usize) -> Self {
+        MangledPtr {
+            ptr: self.ptr.map_addr(|a| a ^ old_key ^ new_key),
+        }
+    }
+
+    /// Gets the mangled address.
+    #[inline]
+    #[must_use]
+    pub fn mangled_addr(&self) -> usize {
+        self.ptr.addr()
+    }
+}
+
+// Manual impls to avoid requiring `T: Clone` and friends.
+impl<T> Copy for XorPtr<T> {}
+impl<T> Clone for XorPtr<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for XorPtr<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.link == other.link
+    }
+}
+impl<T> Eq for XorPtr<T> {}
+
+impl<T> core::fmt::Debug for XorPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "XorPtr({:#x})", self.link)
+    }
+}
+
+impl<T> Copy for MangledPtr<T> {}
+impl<T> Clone for MangledPtr<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for MangledPtr<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr == other.ptr
+    }
+}
+impl<T> Eq for MangledPtr<T> {}
+
+/// Only shows the mangled address, so that it doesn't leak the real one.
+impl<T> core::fmt::Debug for MangledPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "MangledPtr({:#x})", self.mangled_addr())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MangledPtr, XorPtr};
+    #[allow(unused_imports)]
+    use crate::Strict;
+
+    #[test]
+    fn test_xor_ptr() {
+        let mut a = 1u8;
+        let mut b = 2u8;
+        let (pa, pb) = (&mut a as *mut u8, &mut b as *mut u8);
+        let link = XorPtr::new(pa, pb);
+        assert_eq!(link.link(), pa.addr() ^ pb.addr());
+        assert_eq!(link.other(pa), pb);
+        assert_eq!(link.other(pb), pa);
+        unsafe { *link.other(pa) += 1 };
+        assert_eq!(b, 3);
+
+        let end = XorPtr::new(pa, core::ptr::null_mut());
+        assert!(end.other(pa).is_null());
+        assert!(XorPtr::<u8>::default().other(core::ptr::null()).is_null());
+    }
+
+    #[test]
+    fn test_mangled_ptr() {
+        let key = 0xdead_bee0;
+        let mut x = 1u64;
+        let ptr = &mut x as *mut u64;
+
+        let mangled = MangledPtr::new(ptr, key);
+        assert_eq!(mangled.mangled_addr(), ptr.addr() ^ key);
+        assert_eq!(mangled.unmangle(key), ptr);
+        assert_ne!(mangled.unmangle(key + 16), ptr);
+
+        let rekeyed = mangled.rekey(key, 0x1230);
+        unsafe { *rekeyed.unmangle(0x1230) += 1 };
+        assert_eq!(x, 2);
+    }
+}